@@ -0,0 +1,21 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use msbt::{Msbt, ReaderOptions};
+
+use std::io::Cursor;
+
+fuzz_target!(|data: &[u8]| {
+  if let Ok(msbt) = Msbt::from_reader(Cursor::new(data)) {
+    let _ = msbt.to_vec();
+  }
+
+  let options = ReaderOptions {
+    keep_unknown_sections: true,
+    lossy_label_names: true,
+    ..Default::default()
+  };
+  if let Ok(msbt) = Msbt::from_reader_with_options(Cursor::new(data), options) {
+    let _ = msbt.to_vec();
+  }
+});
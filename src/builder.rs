@@ -3,6 +3,7 @@ use crate::{
   Header,
   Msbt,
   SectionTag,
+  error::{Error, Result},
   section::*,
   traits::Updates,
   txt2::Token,
@@ -10,6 +11,11 @@ use crate::{
 
 use byteordered::Endianness;
 
+use std::collections::BTreeMap;
+
+/// The LBL1 group count used when `add_message` has to create LBL1 itself.
+pub const DEFAULT_GROUP_COUNT: u32 = 101;
+
 pub struct MsbtBuilder {
   section_order: Vec<SectionTag>,
   header: Header,
@@ -20,6 +26,8 @@ pub struct MsbtBuilder {
   atr1: Option<Atr1>,
   tsy1: Option<Tsy1>,
   pad_byte: u8,
+  alignment: usize,
+  error: Option<Error>,
 }
 
   macro_rules! add_item {
@@ -37,25 +45,12 @@ pub struct MsbtBuilder {
   }
 
 impl MsbtBuilder {
+  /// Start a file with LBL1 and TXT2 sections if `group_count` is given.
+  ///
+  /// A `group_count` of 0 makes `build` fail with `InvalidGroupCount`.
   pub fn new(endianness: Endianness, encoding: Encoding, group_count: Option<u32>) -> Self {
-    let lbl1 = group_count
-      .map(|gc| {
-        let groups = (0..gc)
-          .map(|_| crate::section::lbl1::Group {
-            label_count: 0,
-            offset: 0,
-          })
-          .collect();
-        Lbl1 {
-          section: Section::new(*b"LBL1", 0),
-          groups,
-          labels: Vec::with_capacity(gc as usize),
-        }
-      });
-    let txt2 = group_count.map(|_| Txt2 {
-      section: Section::new(*b"TXT2", 0),
-      values: vec![],
-    });
+    let lbl1 = group_count.map(new_lbl1);
+    let txt2 = group_count.map(|_| new_txt2(encoding, endianness));
     let (section_count, section_order) = if group_count.is_some() {
       let mut order = Vec::with_capacity(6);
       order.push(SectionTag::Lbl1);
@@ -67,14 +62,8 @@ impl MsbtBuilder {
     MsbtBuilder {
       section_order,
       header: Header {
-        magic: crate::HEADER_MAGIC,
-        endianness,
-        _unknown_1: 0,
-        encoding,
-        _unknown_2: 3,
         section_count,
-        _unknown_3: 0,
-        padding: [0; 10],
+        ..Header::new(endianness, encoding)
       },
       lbl1,
       txt2,
@@ -83,6 +72,8 @@ impl MsbtBuilder {
       atr1: None,
       tsy1: None,
       pad_byte: 0,
+      alignment: crate::DEFAULT_ALIGNMENT,
+      error: group_count.filter(|&count| count == 0).map(Error::InvalidGroupCount),
     }
   }
 
@@ -90,7 +81,105 @@ impl MsbtBuilder {
     &self.header
   }
 
-  pub fn build(self) -> Msbt {
+  pub fn pad_byte(mut self, pad_byte: u8) -> Self {
+    self.pad_byte = pad_byte;
+
+    self
+  }
+
+  /// Pad sections to a multiple of `alignment` bytes instead of `DEFAULT_ALIGNMENT`.
+  pub fn alignment(mut self, alignment: usize) -> Self {
+    self.alignment = alignment;
+
+    self
+  }
+
+  /// Change the byte order, converting any messages and ATO1, ATR1 and TSY1 sections already added.
+  pub fn endianness(mut self, endianness: Endianness) -> Self {
+    if endianness != self.header.endianness {
+      if let Some(ato1) = self.ato1.as_mut() {
+        ato1.swap_byte_order();
+      }
+      if let Some(atr1) = self.atr1.as_mut() {
+        atr1.swap_byte_order();
+      }
+      if let Some(tsy1) = self.tsy1.as_mut() {
+        tsy1.swap_byte_order();
+      }
+    }
+    self.header.endianness = endianness;
+    if let Some(txt2) = self.txt2.as_mut() {
+      txt2.set_endianness(endianness);
+    }
+
+    self
+  }
+
+  /// Change the text encoding, converting any messages already added.
+  ///
+  /// If they cannot be converted, `build` returns the error.
+  pub fn encoding(mut self, encoding: Encoding) -> Self {
+    self.header.encoding = encoding;
+    if let Some(txt2) = self.txt2.as_mut() {
+      if let Err(e) = txt2.convert_encoding(encoding) {
+        self.error.get_or_insert(e);
+      }
+    }
+
+    self
+  }
+
+  /// Add a null-terminated message with the given text.
+  ///
+  /// LBL1 and TXT2 are created if this builder has none, with `DEFAULT_GROUP_COUNT` groups.
+  pub fn add_message<N: Into<String>>(mut self, label: N, text: &str) -> Self {
+    let (encoding, endianness) = (self.header.encoding, self.header.endianness);
+    if self.lbl1.is_none() {
+      self.lbl1 = Some(new_lbl1(DEFAULT_GROUP_COUNT));
+      self.section_order.push(SectionTag::Lbl1);
+    }
+    if self.txt2.is_none() {
+      self.txt2 = Some(new_txt2(encoding, endianness));
+      self.section_order.push(SectionTag::Txt2);
+    }
+
+    self.add_label(label, vec![Token::from_text(text, encoding, endianness)])
+  }
+
+  /// Give the message at TXT2 `index` the NLI1 id `id`, creating NLI1 if needed.
+  pub fn with_nli1_id(mut self, id: u32, index: u32) -> Self {
+    if self.nli1.is_none() {
+      self = self.nli1(Nli1::new_unlinked(0, BTreeMap::new()));
+    }
+    if let Some(nli1) = self.nli1.as_mut() {
+      nli1.insert(index, id);
+    }
+
+    self
+  }
+
+  /// Finish the file, filling in group offsets, section sizes and header counts.
+  ///
+  /// Fails if two messages share a label, if a label is longer than 255 bytes, if an NLI1 id points past
+  /// the last message, or if `encoding` or `txt2` could not convert the messages given to them.
+  pub fn build(self) -> Result<Msbt> {
+    if let Some(e) = self.error {
+      return Err(e);
+    }
+    if let Some(lbl1) = &self.lbl1 {
+      let mut names: Vec<&str> = lbl1.label_names().collect();
+      names.sort_unstable();
+      if let Some(pair) = names.windows(2).find(|pair| pair[0] == pair[1]) {
+        return Err(Error::DuplicateLabel(pair[0].to_string()));
+      }
+    }
+    if let Some(nli1) = &self.nli1 {
+      let message_count = self.txt2.as_ref().map_or(0, |txt2| txt2.values.len());
+      if let Some((&index, _)) = nli1.global_ids.iter().find(|(&index, _)| index as usize >= message_count) {
+        return Err(Error::InvalidNli1Index(index));
+      }
+    }
+
     let mut msbt = Msbt {
       header: self.header,
       section_order: self.section_order,
@@ -100,7 +189,11 @@ impl MsbtBuilder {
       atr1: self.atr1,
       tsy1: self.tsy1,
       txt2: self.txt2,
+      unknown_sections: Vec::new(),
       pad_byte: self.pad_byte,
+      alignment: self.alignment,
+      declared_size_override: None,
+      padding_bytes: Vec::new(),
     };
 
     if let Some(lbl1) = msbt.lbl1.as_mut() {
@@ -109,15 +202,26 @@ impl MsbtBuilder {
     if let Some(txt2) = msbt.txt2.as_mut() {
       txt2.update();
     }
+    if let Some(nli1) = msbt.nli1.as_mut() {
+      nli1.update();
+    }
 
     msbt.update();
+    msbt.header.declared_file_size = msbt.total_size() as u32;
 
-    msbt
+    Ok(msbt)
   }
 
+  /// Add a message, appending a null terminator to `value` if it has none.
   pub fn add_label<N: Into<String>, V: Into<Vec<Token>>>(mut self, name: N, value: V) -> Self {
     let name = name.into();
-    let value = value.into();
+    if let Err(e) = crate::section::lbl1::Label::check_name(&name) {
+      self.error.get_or_insert(e);
+    }
+    let mut value = value.into();
+    if !crate::section::txt2::has_terminator(&value) {
+      value.push(Token::Padding(Vec::new()));
+    }
 
     let lbl1 = match self.lbl1.as_mut() {
       Some(l) => l,
@@ -128,13 +232,13 @@ impl MsbtBuilder {
       None => return self,
     };
 
-    let label = crate::section::lbl1::Label {
-      name,
-    };
+    let label = crate::section::lbl1::Label::new(name);
 
     txt2.values.push(value);
-    let group_index = label.checksum(&lbl1) as usize;
-    lbl1.groups[group_index].label_count += 1;
+    let group_index = label.checksum(lbl1) as usize;
+    if let Some(group) = lbl1.groups.get_mut(group_index) {
+      group.label_count += 1;
+    }
     lbl1.labels.push(label);
 
     self
@@ -144,5 +248,49 @@ impl MsbtBuilder {
   add_item!(ato1, Ato1);
   add_item!(atr1, Atr1);
   add_item!(tsy1, Tsy1);
-  add_item!(txt2, Txt2);
+
+  /// Use `txt2` as the TXT2 section, converting its messages to this builder's encoding and byte order.
+  ///
+  /// If they cannot be converted, `build` returns the error.
+  pub fn txt2(mut self, mut txt2: Txt2) -> Self {
+    txt2.set_endianness(self.header.endianness);
+    if txt2.encoding != self.header.encoding {
+      if let Err(e) = txt2.convert_encoding(self.header.encoding) {
+        self.error.get_or_insert(e);
+      }
+    }
+    if let Some(pos) = self.section_order.iter().position(|x| x == &SectionTag::Txt2) {
+      self.section_order.remove(pos);
+    }
+    self.section_order.push(SectionTag::Txt2);
+    self.txt2 = Some(txt2);
+
+    self
+  }
+}
+
+pub(crate) fn new_lbl1(group_count: u32) -> Lbl1 {
+  let groups = (0..group_count)
+    .map(|_| crate::section::lbl1::Group {
+      label_count: 0,
+      offset: 0,
+    })
+    .collect();
+  Lbl1 {
+    section: Section::new(*b"LBL1", 0),
+    groups,
+    labels: Vec::with_capacity(group_count as usize),
+    read_order: Vec::new(),
+    preserve_order: false,
+  }
+}
+
+fn new_txt2(encoding: Encoding, endianness: Endianness) -> Txt2 {
+  Txt2 {
+    section: Section::new(*b"TXT2", 0),
+    values: vec![],
+    encoding,
+    endianness,
+    read_offsets: Vec::new(),
+  }
 }
@@ -14,7 +14,6 @@ impl<W: Write> Counter<W> {
     }
   }
 
-  #[allow(unused)]
   pub fn into_inner(self) -> W {
     self.writer
   }
@@ -0,0 +1,172 @@
+use crate::{
+  Encoding,
+  Msbt,
+  SectionTag,
+  section::txt2::Token,
+};
+
+use byteordered::Endianness;
+
+use std::collections::BTreeMap;
+
+/// How a message is identified when comparing files.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MessageKey {
+  Label(String),
+  /// Used when either file has no LBL1.
+  Index(usize),
+}
+
+/// A difference in messages between two files, as found by `Msbt::diff`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MsbtDelta {
+  Added(MessageKey),
+  Removed(MessageKey),
+  Changed {
+    key: MessageKey,
+    old: Vec<Token>,
+    new: Vec<Token>,
+  },
+}
+
+/// The magic, size and padding at the start of every section.
+const SECTION_HEADER_LEN: usize = 16;
+
+/// A difference in how two files are laid out, as found by `Msbt::structural_diff`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StructuralDelta {
+  Magic { old: [u8; 8], new: [u8; 8] },
+  Endianness { old: Endianness, new: Endianness },
+  Encoding { old: Encoding, new: Encoding },
+  /// One of the header's numeric fields, named as in `Header`'s getters.
+  HeaderField { field: &'static str, old: u32, new: u32 },
+  HeaderPadding { old: [u8; 10], new: [u8; 10] },
+  SectionOrder { old: Vec<SectionTag>, new: Vec<SectionTag> },
+  SectionAdded(SectionTag),
+  SectionRemoved(SectionTag),
+  /// The size stored in a section's header when written.
+  SectionSize { tag: SectionTag, old: usize, new: usize },
+  /// The 8 bytes after the size in a section's header.
+  SectionPadding { tag: SectionTag, old: [u8; 8], new: [u8; 8] },
+  PadByte { old: u8, new: u8 },
+  Alignment { old: usize, new: usize },
+}
+
+impl Msbt {
+  /// Compare the layout of this file with that of `other`: header fields, which sections are present
+  /// and their sizes and padding.
+  ///
+  /// Sizes are the ones the files would be written with. For sections that appear more than once, only
+  /// the first of each is compared.
+  pub fn structural_diff(&self, other: &Msbt) -> Vec<StructuralDelta> {
+    let mut deltas = Vec::new();
+    let (a, b) = (&self.header, &other.header);
+
+    if a.magic != b.magic {
+      deltas.push(StructuralDelta::Magic { old: a.magic, new: b.magic });
+    }
+    if a.endianness != b.endianness {
+      deltas.push(StructuralDelta::Endianness { old: a.endianness, new: b.endianness });
+    }
+    if a.encoding != b.encoding {
+      deltas.push(StructuralDelta::Encoding { old: a.encoding, new: b.encoding });
+    }
+    let fields = [
+      ("unknown_1", u32::from(a._unknown_1), u32::from(b._unknown_1)),
+      ("unknown_2", u32::from(a._unknown_2), u32::from(b._unknown_2)),
+      ("section_count", self.section_order.len() as u32, other.section_order.len() as u32),
+      ("unknown_3", u32::from(a._unknown_3), u32::from(b._unknown_3)),
+    ];
+    for &(field, old, new) in fields.iter().filter(|(_, old, new)| old != new) {
+      deltas.push(StructuralDelta::HeaderField { field, old, new });
+    }
+    if a.padding != b.padding {
+      deltas.push(StructuralDelta::HeaderPadding { old: a.padding, new: b.padding });
+    }
+
+    if self.section_order != other.section_order {
+      deltas.push(StructuralDelta::SectionOrder {
+        old: self.section_order.clone(),
+        new: other.section_order.clone(),
+      });
+    }
+    let mut tags: Vec<SectionTag> = Vec::new();
+    for tag in self.section_order.iter().chain(&other.section_order) {
+      if !tags.contains(tag) {
+        tags.push(tag.clone());
+      }
+    }
+    for tag in tags {
+      let size = |msbt: &Msbt| msbt.unpadded_section_size(&tag).map(|size| size - SECTION_HEADER_LEN);
+      match (size(self), size(other)) {
+        (Some(_), None) => deltas.push(StructuralDelta::SectionRemoved(tag)),
+        (None, Some(_)) => deltas.push(StructuralDelta::SectionAdded(tag)),
+        (Some(old), Some(new)) => {
+          if old != new {
+            deltas.push(StructuralDelta::SectionSize { tag: tag.clone(), old, new });
+          }
+          let padding = |msbt: &Msbt| msbt.sections()
+            .find(|(t, _)| t == &tag)
+            .map(|(_, section)| section.padding);
+          if let (Some(old), Some(new)) = (padding(self), padding(other)) {
+            if old != new {
+              deltas.push(StructuralDelta::SectionPadding { tag, old, new });
+            }
+          }
+        },
+        (None, None) => {},
+      }
+    }
+
+    if self.pad_byte != other.pad_byte {
+      deltas.push(StructuralDelta::PadByte { old: self.pad_byte, new: other.pad_byte });
+    }
+    if self.alignment != other.alignment {
+      deltas.push(StructuralDelta::Alignment { old: self.alignment, new: other.alignment });
+    }
+
+    deltas
+  }
+
+  /// Compare the messages in this file with those in `other`.
+  ///
+  /// Messages are matched by label, or by TXT2 index if either file has no LBL1.
+  pub fn diff(&self, other: &Msbt) -> Vec<MsbtDelta> {
+    let by_label = self.lbl1.is_some() && other.lbl1.is_some();
+    let old = self.messages_by_key(by_label);
+    let new = other.messages_by_key(by_label);
+
+    let mut deltas = Vec::new();
+    for (key, old_value) in &old {
+      match new.get(key) {
+        None => deltas.push(MsbtDelta::Removed(key.clone())),
+        Some(new_value) if new_value != old_value => deltas.push(MsbtDelta::Changed {
+          key: key.clone(),
+          old: old_value.to_vec(),
+          new: new_value.to_vec(),
+        }),
+        Some(_) => {},
+      }
+    }
+    for key in new.keys() {
+      if !old.contains_key(key) {
+        deltas.push(MsbtDelta::Added(key.clone()));
+      }
+    }
+
+    deltas
+  }
+
+  fn messages_by_key(&self, by_label: bool) -> BTreeMap<MessageKey, &[Token]> {
+    if by_label {
+      return self.entries()
+        .map(|(label, value)| (MessageKey::Label(label.to_string()), value))
+        .collect();
+    }
+
+    self.txt2.iter()
+      .flat_map(|txt2| txt2.values.iter().enumerate())
+      .map(|(i, value)| (MessageKey::Index(i), value.as_slice()))
+      .collect()
+  }
+}
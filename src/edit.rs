@@ -0,0 +1,145 @@
+use crate::{
+  Msbt,
+  SectionTag,
+  builder::DEFAULT_GROUP_COUNT,
+  error::{Error, Result},
+  section::{
+    Lbl1,
+    Nli1,
+    Txt2,
+    lbl1::Label,
+    txt2::Token,
+  },
+  traits::Updates,
+};
+
+use std::collections::BTreeMap;
+
+/// A set of message edits made through `Msbt::edit`.
+///
+/// Sizes, group offsets and counts are only recomputed once, when the edit finishes.
+pub struct EditSession<'a> {
+  lbl1: &'a mut Lbl1,
+  txt2: &'a mut Txt2,
+  nli1: Option<&'a mut Nli1>,
+  /// Label name to index, built on first use and dropped whenever indices shift.
+  names: Option<BTreeMap<String, usize>>,
+}
+
+impl Msbt {
+  /// Make several message edits at once, recomputing sizes and offsets only at the end.
+  ///
+  /// Fails if LBL1 or TXT2 is missing.
+  pub fn edit<T, F>(&mut self, f: F) -> Result<T>
+    where F: FnOnce(&mut EditSession<'_>) -> T,
+  {
+    let lbl1 = self.lbl1.as_mut().ok_or(Error::MissingSection(SectionTag::Lbl1))?;
+    let txt2 = self.txt2.as_mut().ok_or(Error::MissingSection(SectionTag::Txt2))?;
+
+    let mut session = EditSession {
+      lbl1,
+      txt2,
+      nli1: self.nli1.as_mut(),
+      names: None,
+    };
+    let result = f(&mut session);
+    session.finish();
+
+    Ok(result)
+  }
+}
+
+impl<'a> EditSession<'a> {
+  /// The TXT2 index of the message with the given label.
+  pub fn index_of(&mut self, label: &str) -> Option<usize> {
+    if label.is_empty() {
+      return None;
+    }
+
+    let lbl1 = &self.lbl1;
+    self.names
+      .get_or_insert_with(|| lbl1.name_to_index())
+      .get(label)
+      .copied()
+  }
+
+  /// Add a new message with the given label, returning its TXT2 index.
+  ///
+  /// An empty LBL1 without groups is given `DEFAULT_GROUP_COUNT` groups first. Fails with
+  /// `DuplicateLabelIndex` if LBL1 already has a named label at the new index, which points past the last
+  /// message.
+  pub fn insert(&mut self, label: &str, value: Vec<Token>) -> Result<usize> {
+    if self.index_of(label).is_some() {
+      return Err(Error::DuplicateLabel(label.to_string()));
+    }
+    Label::check_name(label)?;
+    if self.lbl1.groups.is_empty() {
+      self.lbl1.rebuild_groups(DEFAULT_GROUP_COUNT)?;
+    }
+
+    let index = self.txt2.values.len();
+    self.lbl1.set_label_at(index, Label::new(label.to_string()))?;
+    self.txt2.values.push(value);
+    if let Some(names) = self.names.as_mut() {
+      names.insert(label.to_string(), index);
+    }
+
+    Ok(index)
+  }
+
+  /// Remove the message with the given label, returning its TXT2 value.
+  ///
+  /// Every later message moves down one index, including any NLI1 entries pointing at them.
+  pub fn remove(&mut self, label: &str) -> Result<Vec<Token>> {
+    let index = self.index_of(label)
+      .filter(|&i| i < self.txt2.values.len())
+      .ok_or_else(|| Error::UnknownLabel(label.to_string()))?;
+
+    let value = self.txt2.values.remove(index);
+    self.lbl1.labels.remove(index);
+    self.lbl1.remove_from_read_order(index as u32);
+    self.names = None;
+
+    if let Some(nli1) = self.nli1.as_mut() {
+      let index = index as u32;
+      let global_ids = std::mem::take(&mut nli1.global_ids).into_iter()
+        .filter(|&(i, _)| i != index)
+        .map(|(i, id)| if i > index { (i - 1, id) } else { (i, id) })
+        .collect();
+      nli1.set_global_ids(global_ids);
+    }
+
+    Ok(value)
+  }
+
+  /// Replace the value of the message with the given label, returning the old value.
+  pub fn set(&mut self, label: &str, value: Vec<Token>) -> Result<Vec<Token>> {
+    match self.index_of(label).and_then(|i| self.txt2.values.get_mut(i)) {
+      Some(old) => Ok(std::mem::replace(old, value)),
+      None => Err(Error::UnknownLabel(label.to_string())),
+    }
+  }
+
+  /// Rename the label `old` to `new`, keeping its value.
+  ///
+  /// The label moves to the group its new name hashes to when the edit finishes.
+  pub fn rename(&mut self, old: &str, new: &str) -> Result<()> {
+    let index = self.index_of(old).ok_or_else(|| Error::UnknownLabel(old.to_string()))?;
+    if self.index_of(new).is_some() {
+      return Err(Error::DuplicateLabel(new.to_string()));
+    }
+    Label::check_name(new)?;
+
+    self.lbl1.labels[index].set_name(new);
+    self.names = None;
+    Ok(())
+  }
+
+  fn finish(self) {
+    self.lbl1.update();
+    self.txt2.update();
+    if let Some(nli1) = self.nli1 {
+      nli1.update();
+    }
+  }
+}
@@ -1,3 +1,5 @@
+use crate::SectionTag;
+
 use thiserror::Error;
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -5,19 +7,67 @@ pub type Result<T> = std::result::Result<T, Error>;
 #[derive(Debug, Error)]
 pub enum Error {
   #[error("io error: {0}")]
-  Io(std::io::Error),
-  #[error("invalid magic bytes")]
-  InvalidMagic,
-  #[error("invalid BOM")]
+  Io(#[source] std::io::Error),
+  #[error("invalid magic bytes at {offset:#x}")]
+  InvalidMagic { offset: u64 },
+  #[error("invalid byte order mark, expected FE FF or FF FE")]
   InvalidBom,
-  #[error("invalid encoding: {0}")]
+  #[error("unsupported encoding: {0:#04x}")]
   InvalidEncoding(u8),
+  #[error("invalid utf-8 at {offset:#x}: {source}")]
+  InvalidUtf8 { offset: u64, source: std::string::FromUtf8Error },
   #[error("invalid utf-8: {0}")]
-  InvalidUtf8(std::string::FromUtf8Error),
-  #[error("invalid borrowed utf-8: {0}")]
-  InvalidBorrowedUtf8(std::str::Utf8Error),
+  InvalidBorrowedUtf8(#[source] std::str::Utf8Error),
   #[error("invalid utf-16: {0}")]
-  InvalidUtf16(std::string::FromUtf16Error),
-  #[error("invalid section header: {0:?}")]
-  InvalidSection([u8; 4]),
+  InvalidUtf16(#[source] std::string::FromUtf16Error),
+  #[error("utf-16 text has an odd length of {0} bytes")]
+  OddUtf16Length(usize),
+  #[error("unknown section {:?} at {offset:#x}", String::from_utf8_lossy(.magic))]
+  InvalidSection { magic: [u8; 4], offset: u64 },
+  #[error("section {:?} is {size} bytes, which is more than the rest of the file", String::from_utf8_lossy(.magic))]
+  SectionTooLarge { magic: [u8; 4], size: u32 },
+  #[error("invalid TXT2 offset: {0:#x}")]
+  InvalidTxt2Offset(u32),
+  #[error("{count} entries do not fit in the remaining {remaining} bytes")]
+  TooManyEntries { count: u64, remaining: u64 },
+  #[error("file is {size} bytes, which is more than the limit of {max}")]
+  FileTooLarge { size: u64, max: u64 },
+  #[error("file size mismatch: header says {stored} bytes, but contents are {computed} bytes")]
+  SizeMismatch { stored: u32, computed: u32 },
+  #[error("wrote {written} bytes, but the file should be {expected} bytes")]
+  WrittenSizeMismatch { expected: usize, written: usize },
+  #[error("missing section: {0:?}")]
+  MissingSection(SectionTag),
+  #[error("second {tag:?} section at {offset:#x}")]
+  DuplicateSection { tag: SectionTag, offset: u64 },
+  #[error("section order does not match the sections present: {0:?}")]
+  InvalidSectionOrder(Vec<SectionTag>),
+  #[error("duplicate label: {0}")]
+  DuplicateLabel(String),
+  #[error("label is {} bytes, but at most 255 fit: {0}", .0.len())]
+  LabelTooLong(String),
+  #[error("duplicate label index: {0}")]
+  DuplicateLabelIndex(u32),
+  #[error("label index {index} is out of range for {count} labels")]
+  LabelIndexOutOfRange { index: u32, count: usize },
+  #[error("LBL1 must have at least one group, got {0}")]
+  InvalidGroupCount(u32),
+  #[error("{names} label names were given for {indices} indices")]
+  LabelIndexCountMismatch { names: usize, indices: usize },
+  #[error("attribute is {actual} bytes, but ATR1 entries are {expected} bytes")]
+  InvalidAttributeSize { expected: u32, actual: u32 },
+  #[error("duplicate message id: {0}")]
+  DuplicateMessageId(u32),
+  #[error("NLI1 entry points at missing TXT2 index {0}")]
+  InvalidNli1Index(u32),
+  #[error("no TXT2 value at index {0}")]
+  InvalidMessageIndex(usize),
+  #[error("value is {new} bytes, but the one it replaces is {old} bytes")]
+  LengthChanged { old: usize, new: usize },
+  #[error("value cannot be shortened to {max} bytes, as its terminator alone is {min} bytes")]
+  CannotTruncate { max: usize, min: usize },
+  #[error("unknown label: {0}")]
+  UnknownLabel(String),
+  #[error("invalid text on line {0}")]
+  InvalidTextLine(usize),
 }
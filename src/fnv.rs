@@ -0,0 +1,31 @@
+/// A 64-bit FNV-1a hash, used where a hash has to stay the same across platforms and Rust versions.
+#[derive(Debug)]
+pub struct Fnv1a(u64);
+
+impl Fnv1a {
+  const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+  const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+  pub fn new() -> Self {
+    Fnv1a(Fnv1a::OFFSET_BASIS)
+  }
+
+  pub fn write(&mut self, bytes: &[u8]) {
+    for &b in bytes {
+      self.0 = (self.0 ^ u64::from(b)).wrapping_mul(Fnv1a::PRIME);
+    }
+  }
+
+  pub fn write_u32(&mut self, value: u32) {
+    self.write(&value.to_le_bytes());
+  }
+
+  /// Write a length, so that adjacent variable-length fields cannot run into each other.
+  pub fn write_len(&mut self, len: usize) {
+    self.write(&(len as u64).to_le_bytes());
+  }
+
+  pub fn finish(&self) -> u64 {
+    self.0
+  }
+}
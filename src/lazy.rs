@@ -0,0 +1,54 @@
+use crate::{
+  Msbt,
+  MsbtReader,
+  error::Result,
+  section::txt2::{LazyTxt2, Token},
+};
+
+use std::io::{Read, Seek};
+
+/// A file whose TXT2 values are only decoded when asked for.
+///
+/// This keeps the raw TXT2 bytes instead of a token list per value, which uses much less memory when only
+/// a few messages of a large file are needed.
+#[derive(Debug, Clone)]
+pub struct LazyMsbt {
+  msbt: Msbt,
+  txt2: Option<LazyTxt2>,
+}
+
+impl Msbt {
+  /// Read a file from `reader` like `from_reader`, but without decoding the TXT2 values.
+  pub fn from_reader_lazy<R: Read + Seek>(reader: R) -> Result<LazyMsbt> {
+    let mut reader = MsbtReader::new(reader)?;
+    let txt2 = reader.read_sections_lazy()?;
+    Ok(LazyMsbt {
+      msbt: reader.into_msbt(),
+      txt2,
+    })
+  }
+}
+
+impl LazyMsbt {
+  /// The file without its TXT2, which is in `txt2` until `into_msbt` decodes it.
+  pub fn msbt(&self) -> &Msbt {
+    &self.msbt
+  }
+
+  pub fn txt2(&self) -> Option<&LazyTxt2> {
+    self.txt2.as_ref()
+  }
+
+  /// Decode the value of the message with the given label.
+  pub fn get(&self, label: &str) -> Option<Vec<Token>> {
+    let index = self.msbt.lbl1()?.index_of(label)?;
+    self.txt2.as_ref()?.decode(index)
+  }
+
+  /// Decode every TXT2 value, giving the same `Msbt` that `from_reader` would have.
+  pub fn into_msbt(self) -> Msbt {
+    let mut msbt = self.msbt;
+    msbt.txt2 = self.txt2.map(LazyTxt2::into_txt2);
+    msbt
+  }
+}
@@ -1,37 +1,64 @@
 use std::{
-  collections::BTreeMap,
-  io::{Read, Seek, SeekFrom, Write},
-  convert::TryFrom,
+  collections::{BTreeMap, BTreeSet},
+  io::{Cursor, Read, Seek, SeekFrom, Write},
+};
+#[cfg(feature = "fs")]
+use std::{
+  fs::File,
+  io::{BufReader, BufWriter},
+  path::Path,
 };
 
 use byteordered::{Endianness, Endian};
 
+#[cfg(feature = "serde_support")]
+use serde_derive::{Deserialize, Serialize};
+
 mod counter;
+mod fnv;
 mod traits;
 pub mod builder;
+pub mod diff;
+pub mod edit;
 pub mod error;
+pub mod lazy;
 pub mod section;
+pub mod text;
 pub mod updater;
+pub mod validation;
 
 use self::{
+  builder::MsbtBuilder,
   counter::Counter,
+  fnv::Fnv1a,
   error::{Error, Result},
   section::{
     *,
     lbl1::{Group, Label},
+    txt2::LazyTxt2,
   },
   traits::{CalculatesSize, Updates},
   updater::Updater,
 };
 
+#[cfg(feature = "serde_support")]
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Endianness")]
+pub(crate) enum EndiannessDef {
+  Big,
+  Little,
+}
+
 const HEADER_MAGIC: [u8; 8] = *b"MsgStdBn";
 // const LABEL_HASH_MAGIC: u16 = 0x492;
 // const LABEL_MAX_LEN: u8 = 64;
 // const BYTE_ORDER_OFFSET: u8 = 0x8;
 // const HEADER_SIZE: u8 = 0x20;
-const PADDING_LENGTH: usize = 16;
+/// The alignment sections are padded to unless told otherwise.
+pub const DEFAULT_ALIGNMENT: usize = 16;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
 pub enum SectionTag {
   Lbl1,
   Nli1,
@@ -39,9 +66,32 @@ pub enum SectionTag {
   Atr1,
   Tsy1,
   Txt2,
+  /// A section not otherwise recognised, identified by its magic.
+  Unknown([u8; 4]),
 }
 
-#[derive(Debug, Clone)]
+/// Options controlling how lenient reading is.
+#[derive(Debug, Clone, Default)]
+pub struct ReaderOptions {
+  /// Keep sections with unrecognised magics as `UnknownSection`s instead of failing.
+  pub keep_unknown_sections: bool,
+  /// Fail if the last section is missing some or all of its alignment padding, and keep the exact
+  /// padding bytes after each section so they are written back unchanged.
+  pub strict_padding: bool,
+  /// The alignment sections are padded to, or `DEFAULT_ALIGNMENT` if `None`.
+  pub alignment: Option<usize>,
+  /// Fail if LBL1 contains two labels with the same name or the same index.
+  pub reject_duplicate_labels: bool,
+  /// Add unnamed labels to fit a label index past the label count instead of failing with
+  /// `LabelIndexOutOfRange`.
+  pub grow_labels: bool,
+  /// Read label names that are not valid UTF-8 lossily instead of failing. The original bytes are kept
+  /// and written back unchanged.
+  pub lossy_label_names: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
 pub struct Msbt {
   pub(crate) header: Header,
   pub(crate) section_order: Vec<SectionTag>,
@@ -51,17 +101,93 @@ pub struct Msbt {
   pub(crate) atr1: Option<Atr1>,
   pub(crate) tsy1: Option<Tsy1>,
   pub(crate) txt2: Option<Txt2>,
+  pub(crate) unknown_sections: Vec<UnknownSection>,
   pub(crate) pad_byte: u8,
+  pub(crate) alignment: usize,
+  pub(crate) declared_size_override: Option<u32>,
+  /// The alignment padding read after each section in `section_order`, kept only when reading with
+  /// `strict_padding`. Missing entries, or ones no longer the right length, are filled with `pad_byte` when
+  /// writing.
+  pub(crate) padding_bytes: Vec<Vec<u8>>,
 }
 
 impl Msbt {
+  /// Create an empty MSBT with no sections.
+  pub fn new(endianness: Endianness, encoding: Encoding) -> Self {
+    MsbtBuilder::new(endianness, encoding, None).build()
+      .expect("a file with no sections always builds")
+  }
+
+  /// Read a file from `reader`.
+  ///
+  /// Only `Read + Seek` is needed, so in-memory data works through `std::io::Cursor` without touching the
+  /// file system. See also `parse_slice`.
+  ///
+  /// Malformed input of any kind gives an `Err` rather than a panic, and allocations are bounded by the
+  /// size of the input, so this is safe to call on untrusted data. `fuzz/` holds a cargo-fuzz target for it.
   pub fn from_reader<R: Read + Seek>(reader: R) -> Result<Self> {
-    MsbtReader::new(reader).and_then(|m| Ok(m.msbt))
+    Msbt::from_reader_with_options(reader, ReaderOptions::default())
+  }
+
+  pub fn from_reader_with_options<R: Read + Seek>(reader: R, options: ReaderOptions) -> Result<Self> {
+    let mut reader = MsbtReader::with_options(reader, options)?;
+    reader.read_sections()?;
+    Ok(reader.into_msbt())
+  }
+
+  /// Read a file from `reader`, failing with `FileTooLarge` before reading anything if the stream is longer
+  /// than `max_bytes`.
+  pub fn from_reader_limited<R: Read + Seek>(mut reader: R, max_bytes: u64) -> Result<Self> {
+    let pos = reader.stream_position().map_err(Error::Io)?;
+    let size = reader.seek(SeekFrom::End(0)).map_err(Error::Io)?.saturating_sub(pos);
+    if size > max_bytes {
+      return Err(Error::FileTooLarge { size, max: max_bytes });
+    }
+    reader.seek(SeekFrom::Start(pos)).map_err(Error::Io)?;
+
+    Msbt::from_reader(reader)
   }
 
-  pub fn write_to<W: Write>(&self, writer: W) -> Result<()> {
+  /// Read only the 32-byte header at the start of `reader`, without reading any sections.
+  pub fn read_header<R: Read>(mut reader: R) -> Result<Header> {
+    Header::from_reader(&mut reader)
+  }
+
+  /// Read as much of a file as possible, returning the sections read before any error along with the error.
+  ///
+  /// Only an unreadable header is returned as `Err`, since there is nothing to salvage without it.
+  pub fn from_reader_partial<R: Read + Seek>(reader: R) -> Result<(Self, Option<Error>)> {
+    let mut reader = MsbtReader::new(reader)?;
+    let error = reader.read_sections().err();
+    Ok((reader.into_msbt(), error))
+  }
+
+  /// Read a file from a reader that cannot seek, such as stdin or a socket.
+  ///
+  /// The whole stream is read into memory before parsing, so this holds a full copy of the file alongside
+  /// the parsed result. Prefer `from_reader` when the source can seek.
+  pub fn from_read<R: Read>(mut reader: R) -> Result<Self> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes).map_err(Error::Io)?;
+    Msbt::parse_slice(&bytes)
+  }
+
+  /// Read a file from bytes already in memory.
+  pub fn parse_slice(bytes: &[u8]) -> Result<Self> {
+    Msbt::from_reader(Cursor::new(bytes))
+  }
+
+  /// Write this file to `writer`, returning the number of bytes written.
+  ///
+  /// Fails with `LabelTooLong` before writing LBL1 if a label name is longer than 255 bytes.
+  ///
+  /// Fails with `WrittenSizeMismatch` if the bytes written differ from `total_size`, which means the output
+  /// is corrupt. The bytes have already been written to `writer` by then. This is independent of the file
+  /// size written in the header, which `set_declared_size_override` can change.
+  pub fn write_to<W: Write>(&self, writer: W) -> Result<usize> {
     let mut writer = MsbtWriter::new(self, writer);
     writer.write_header()?;
+    let mut unknown_sections = self.unknown_sections.iter();
     for tag in &self.section_order {
       match *tag {
         SectionTag::Lbl1 => writer.write_lbl1()?,
@@ -70,24 +196,146 @@ impl Msbt {
         SectionTag::Atr1 => writer.write_atr1()?,
         SectionTag::Tsy1 => writer.write_tsy1()?,
         SectionTag::Txt2 => writer.write_txt2()?,
+        SectionTag::Unknown(_) => if let Some(section) = unknown_sections.next() {
+          writer.write_unknown(section)?;
+        },
       }
     }
-    Ok(())
+
+    let written = writer.into_inner().1;
+    let expected = self.calc_size();
+    if written != expected {
+      return Err(Error::WrittenSizeMismatch { expected, written });
+    }
+    Ok(written)
+  }
+
+  #[cfg(feature = "fs")]
+  pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+    let file = File::open(path).map_err(Error::Io)?;
+    Msbt::from_reader(BufReader::new(file))
+  }
+
+  #[cfg(feature = "fs")]
+  pub fn to_path<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+    let file = File::create(path).map_err(Error::Io)?;
+    let mut writer = BufWriter::new(file);
+    self.write_to(&mut writer)?;
+    writer.flush().map_err(Error::Io)
+  }
+
+  /// Write this file into a new `Vec`.
+  ///
+  /// For files read with `from_reader`, this reproduces the original bytes as long as labels were stored
+  /// sorted by group and TXT2 values were stored in index order.
+  pub fn to_vec(&self) -> Result<Vec<u8>> {
+    let mut buf = Vec::with_capacity(self.calc_size());
+    self.write_to(&mut buf)?;
+    Ok(buf)
   }
 
   pub fn header(&self) -> &Header {
     &self.header
   }
 
+  pub fn header_mut(&mut self) -> &mut Header {
+    &mut self.header
+  }
+
   pub fn section_order(&self) -> &[SectionTag] {
     &self.section_order
   }
 
+  /// The sections this file contains, in `section_order` order, followed by any that are present but
+  /// missing from it.
+  pub fn present_sections(&self) -> Vec<SectionTag> {
+    let mut present: Vec<SectionTag> = self.section_order.iter()
+      .filter(|tag| self.has_section(tag))
+      .cloned()
+      .collect();
+    let known = [
+      SectionTag::Lbl1,
+      SectionTag::Nli1,
+      SectionTag::Ato1,
+      SectionTag::Atr1,
+      SectionTag::Tsy1,
+      SectionTag::Txt2,
+    ];
+    for tag in known.iter() {
+      if self.has_section(tag) && !present.contains(tag) {
+        present.push(tag.clone());
+      }
+    }
+    present
+  }
+
+  /// The header of each section in `section_order`, skipping any that are listed but missing.
+  pub fn sections(&self) -> impl Iterator<Item = (SectionTag, &Section)> {
+    let mut unknown = self.unknown_sections.iter();
+    self.section_order.iter().filter_map(move |tag| {
+      let section = match tag {
+        SectionTag::Lbl1 => self.lbl1.as_ref().map(|lbl1| &lbl1.section),
+        SectionTag::Nli1 => self.nli1.as_ref().map(|nli1| &nli1.section),
+        SectionTag::Ato1 => self.ato1.as_ref().map(|ato1| &ato1.section),
+        SectionTag::Atr1 => self.atr1.as_ref().map(|atr1| &atr1.section),
+        SectionTag::Tsy1 => self.tsy1.as_ref().map(|tsy1| &tsy1.section),
+        SectionTag::Txt2 => self.txt2.as_ref().map(|txt2| &txt2.section),
+        SectionTag::Unknown(_) => unknown.next().map(|section| &section.section),
+      };
+      section.map(|section| (tag.clone(), section))
+    })
+  }
+
+  /// Whether this file contains the given section.
+  pub fn has_section(&self, tag: &SectionTag) -> bool {
+    match tag {
+      SectionTag::Lbl1 => self.lbl1.is_some(),
+      SectionTag::Nli1 => self.nli1.is_some(),
+      SectionTag::Ato1 => self.ato1.is_some(),
+      SectionTag::Atr1 => self.atr1.is_some(),
+      SectionTag::Tsy1 => self.tsy1.is_some(),
+      SectionTag::Txt2 => self.txt2.is_some(),
+      SectionTag::Unknown(magic) => self.unknown_sections.iter().any(|section| &section.magic() == magic),
+    }
+  }
+
+  /// Change the order sections are written in.
+  ///
+  /// `order` must list exactly the sections already in `section_order`, each as many times as it appears
+  /// there.
+  pub fn set_section_order(&mut self, order: &[SectionTag]) -> Result<()> {
+    let mut remaining = self.section_order.clone();
+    for tag in order {
+      match remaining.iter().position(|t| t == tag) {
+        Some(pos) => { remaining.remove(pos); },
+        None => return Err(Error::InvalidSectionOrder(order.to_vec())),
+      }
+    }
+    if !remaining.is_empty() {
+      return Err(Error::InvalidSectionOrder(order.to_vec()));
+    }
+
+    // unknown sections are written in sequence, so they have to follow the new order too
+    let mut unknown = std::mem::take(&mut self.unknown_sections);
+    for tag in order {
+      if let SectionTag::Unknown(magic) = tag {
+        if let Some(pos) = unknown.iter().position(|section| &section.magic() == magic) {
+          self.unknown_sections.push(unknown.remove(pos));
+        }
+      }
+    }
+    self.unknown_sections.append(&mut unknown);
+
+    self.section_order = order.to_vec();
+    self.padding_bytes.clear();
+    Ok(())
+  }
+
   pub fn lbl1(&self) -> Option<&Lbl1> {
     self.lbl1.as_ref()
   }
 
-  pub fn lbl1_mut(&mut self) -> Option<Updater<Lbl1>> {
+  pub fn lbl1_mut(&mut self) -> Option<Updater<'_, Lbl1>> {
     self.lbl1.as_mut().map(Updater::new)
   }
 
@@ -95,8 +343,8 @@ impl Msbt {
     self.nli1.as_ref()
   }
 
-  pub fn nli1_mut(&mut self) -> Option<&mut Nli1> {
-    self.nli1.as_mut()
+  pub fn nli1_mut(&mut self) -> Option<Updater<'_, Nli1>> {
+    self.nli1.as_mut().map(Updater::new)
   }
 
   pub fn ato1(&self) -> Option<&Ato1> {
@@ -127,29 +375,439 @@ impl Msbt {
     self.txt2.as_ref()
   }
 
-  pub fn txt2_mut(&mut self) -> Option<Updater<Txt2>> {
+  /// Edit TXT2. The section size is recomputed from the values when the returned `Updater` is dropped.
+  pub fn txt2_mut(&mut self) -> Option<Updater<'_, Txt2>> {
     self.txt2.as_mut().map(Updater::new)
   }
 
-  fn plus_padding(size: usize) -> usize {
-    let rem = size % PADDING_LENGTH;
-    if rem > 0 {
-      size + (PADDING_LENGTH - rem)
-    } else {
-      size
+  /// Sections with unrecognised magics, in the order they appear in `section_order`.
+  pub fn unknown_sections(&self) -> &[UnknownSection] {
+    &self.unknown_sections
+  }
+
+  /// The byte used to pad sections to their alignment.
+  pub fn pad_byte(&self) -> u8 {
+    self.pad_byte
+  }
+
+  pub fn set_pad_byte(&mut self, pad_byte: u8) {
+    self.pad_byte = pad_byte;
+  }
+
+  /// The alignment sections are padded to. Values of 0 and 1 mean no padding.
+  pub fn alignment(&self) -> usize {
+    self.alignment
+  }
+
+  pub fn set_alignment(&mut self, alignment: usize) {
+    self.alignment = alignment;
+  }
+
+  pub fn declared_size_override(&self) -> Option<u32> {
+    self.declared_size_override
+  }
+
+  /// Write `size` as the file size in the header instead of the real size, or go back to the real size
+  /// with `None`.
+  ///
+  /// Only the header field changes; the contents written are the same either way.
+  pub fn set_declared_size_override(&mut self, size: Option<u32>) {
+    self.declared_size_override = size;
+  }
+
+  /// The number of TXT2 values, or 0 if there is no TXT2 section.
+  pub fn message_count(&self) -> usize {
+    self.txt2.as_ref().map_or(0, |txt2| txt2.values.len())
+  }
+
+  /// The number of LBL1 labels, or 0 if there is no LBL1 section.
+  pub fn label_count(&self) -> usize {
+    self.lbl1.as_ref().map_or(0, |lbl1| lbl1.labels.len())
+  }
+
+  /// Iterate over each label name together with its TXT2 value.
+  ///
+  /// Yields nothing if either LBL1 or TXT2 is missing.
+  pub fn entries(&self) -> impl Iterator<Item = (&str, &[txt2::Token])> {
+    self.lbl1.iter()
+      .zip(self.txt2.iter())
+      .flat_map(|(lbl1, txt2)| lbl1.labels.iter().zip(txt2.values.iter()))
+      .filter(|(label, _)| !label.name.is_empty())
+      .map(|(label, value)| (label.name.as_str(), value.as_slice()))
+  }
+
+  /// Find every message whose text contains `needle`, returning its TXT2 index and decoded text.
+  ///
+  /// Control codes are ignored when matching.
+  pub fn find_text(&self, needle: &str, case_insensitive: bool) -> Vec<(usize, String)> {
+    let txt2 = match self.txt2.as_ref() {
+      Some(txt2) => txt2,
+      None => return Vec::new(),
+    };
+
+    let needle = if case_insensitive { needle.to_lowercase() } else { needle.to_string() };
+    (0..txt2.values.len())
+      .filter_map(|i| txt2.get_string(i, None).map(|text| (i, text)))
+      .filter(|(_, text)| if case_insensitive {
+        text.to_lowercase().contains(&needle)
+      } else {
+        text.contains(&needle)
+      })
+      .collect()
+  }
+
+  /// Decode every TXT2 value in index order, with control codes written as `{group.tag:params}` like
+  /// `Txt2::display`.
+  ///
+  /// Fails on text that is not valid in the file's encoding. Gives an empty list if there is no TXT2.
+  pub fn to_strings(&self) -> Result<Vec<String>> {
+    let txt2 = match self.txt2.as_ref() {
+      Some(txt2) => txt2,
+      None => return Ok(Vec::new()),
+    };
+
+    let mut strings = Vec::with_capacity(txt2.values.len());
+    for (i, value) in txt2.values.iter().enumerate() {
+      for token in value {
+        if let txt2::Token::Text(bytes) = token {
+          txt2::try_decode_text(bytes, txt2.encoding, txt2.endianness)?;
+        }
+      }
+      strings.push(txt2.display(i).expect("index is in range").to_string());
+    }
+
+    Ok(strings)
+  }
+
+  /// A hash of the labels, TXT2 values and NLI1 ids, which stays the same between runs and platforms.
+  ///
+  /// Padding, section order and header fields are not included, so files that differ only in layout hash
+  /// the same.
+  pub fn content_hash(&self) -> u64 {
+    let mut hasher = Fnv1a::new();
+
+    if let Some(lbl1) = &self.lbl1 {
+      hasher.write(b"LBL1");
+      hasher.write_len(lbl1.labels.len());
+      for label in &lbl1.labels {
+        hasher.write_len(label.name_bytes().len());
+        hasher.write(label.name_bytes());
+      }
+    }
+
+    if let Some(txt2) = &self.txt2 {
+      hasher.write(b"TXT2");
+      hasher.write_len(txt2.values.len());
+      for value in &txt2.values {
+        hasher.write_len(value.len());
+        for token in value {
+          match token {
+            txt2::Token::TagStart(group, tag, params) => {
+              hasher.write(&[0]);
+              hasher.write_u32(u32::from(*group));
+              hasher.write_u32(u32::from(*tag));
+              hasher.write_len(params.len());
+              hasher.write(params);
+            },
+            txt2::Token::Text(bytes) => {
+              hasher.write(&[1]);
+              hasher.write_len(bytes.len());
+              hasher.write(bytes);
+            },
+            txt2::Token::TagEnd => hasher.write(&[2]),
+            txt2::Token::Padding(bytes) => {
+              hasher.write(&[3]);
+              hasher.write_len(bytes.len());
+              hasher.write(bytes);
+            },
+          }
+        }
+      }
+    }
+
+    if let Some(nli1) = &self.nli1 {
+      hasher.write(b"NLI1");
+      hasher.write_len(nli1.global_ids.len());
+      for (&index, &id) in &nli1.global_ids {
+        hasher.write_u32(index);
+        hasher.write_u32(id);
+      }
+    }
+
+    hasher.finish()
+  }
+
+  /// Replace the TXT2 value at `index` with one of the same length in bytes, without recomputing any
+  /// sizes or offsets.
+  ///
+  /// Fails with `LengthChanged` if `value` would be written as a different number of bytes.
+  pub fn patch_message_in_place(&mut self, index: usize, value: Vec<txt2::Token>) -> Result<()> {
+    let txt2 = self.txt2.as_mut().ok_or(Error::MissingSection(SectionTag::Txt2))?;
+    let encoding = txt2.encoding;
+    let old = txt2.values.get_mut(index).ok_or(Error::InvalidMessageIndex(index))?;
+
+    let byte_len = |value: &[txt2::Token]| value.iter().map(|token| token.byte_len(encoding)).sum::<usize>();
+    let (old_len, new_len) = (byte_len(old), byte_len(&value));
+    if old_len != new_len {
+      return Err(Error::LengthChanged { old: old_len, new: new_len });
+    }
+
+    *old = value;
+    Ok(())
+  }
+
+  /// Add a new message with the given label, returning its TXT2 index.
+  ///
+  /// LBL1 is created with `builder::DEFAULT_GROUP_COUNT` groups if the file has none. Use `edit` when
+  /// adding many messages, so sizes and offsets are only recomputed once.
+  pub fn insert_message(&mut self, label: &str, value: Vec<txt2::Token>) -> Result<usize> {
+    if self.txt2.is_none() {
+      return Err(Error::MissingSection(SectionTag::Txt2));
+    }
+    self.ensure_lbl1(builder::DEFAULT_GROUP_COUNT)?;
+    self.edit(|session| session.insert(label, value))?
+  }
+
+  /// Add a new message with the NLI1 id `id`, returning its TXT2 index.
+  ///
+  /// NLI1 is created if the file has none. If the file also has LBL1, the message gets an unnamed label,
+  /// which is written as described in `ensure_lbl1`. Fails with `DuplicateLabelIndex` if LBL1 already has a
+  /// named label at the new index, which points past the last message.
+  pub fn insert_nli1_message(&mut self, id: u32, value: Vec<txt2::Token>) -> Result<usize> {
+    let txt2 = self.txt2.as_mut().ok_or(Error::MissingSection(SectionTag::Txt2))?;
+    if self.nli1.as_ref().and_then(|nli1| nli1.txt2_index(id)).is_some() {
+      return Err(Error::DuplicateMessageId(id));
+    }
+
+    let index = txt2.values.len();
+    if let Some(lbl1) = self.lbl1.as_mut() {
+      if lbl1.groups.is_empty() {
+        lbl1.rebuild_groups(builder::DEFAULT_GROUP_COUNT)?;
+      }
+      lbl1.set_label_at(index, Label::new(String::new()))?;
+      lbl1.update();
+    }
+
+    txt2.values.push(value);
+    txt2.update();
+
+    if self.nli1.is_none() {
+      self.nli1 = Some(Nli1::new_unlinked(0, BTreeMap::new()));
+      self.insert_section_tag(SectionTag::Nli1);
+    }
+    if let Some(nli1) = self.nli1.as_mut() {
+      nli1.insert(index as u32, id);
+      nli1.update();
+    }
+
+    Ok(index)
+  }
+
+  /// Add an LBL1 section with `group_count` groups if the file has none, returning it.
+  ///
+  /// Every existing TXT2 value gets an unnamed label, so later labels line up with their values. Unnamed
+  /// labels are written out like any other, with an empty name in group 0, because readers expect every
+  /// label index to be below the LBL1 label count. They are left out of `Lbl1::label_names` and lookups by
+  /// name.
+  ///
+  /// Fails with `InvalidGroupCount` if LBL1 has to be added and `group_count` is 0.
+  pub fn ensure_lbl1(&mut self, group_count: u32) -> Result<Updater<'_, Lbl1>> {
+    if self.lbl1.is_none() {
+      if group_count == 0 {
+        return Err(Error::InvalidGroupCount(group_count));
+      }
+      let mut lbl1 = builder::new_lbl1(group_count);
+      let value_count = self.txt2.as_ref().map_or(0, |txt2| txt2.values.len());
+      lbl1.labels = vec![Label::new(String::new()); value_count];
+      self.lbl1 = Some(lbl1);
+      self.insert_section_tag(SectionTag::Lbl1);
     }
+
+    Ok(Updater::new(self.lbl1.as_mut().expect("LBL1 was just added")))
+  }
+
+  /// Add `tag` to `section_order` just before TXT2, or at the end if there is no TXT2.
+  fn insert_section_tag(&mut self, tag: SectionTag) {
+    let pos = self.section_order.iter()
+      .position(|t| t == &SectionTag::Txt2)
+      .unwrap_or(self.section_order.len());
+    self.section_order.insert(pos, tag);
+    self.padding_bytes.clear();
+    self.update();
+  }
+
+  /// Remove the message with the given label, returning its TXT2 value.
+  ///
+  /// Every later message moves down one index, including any NLI1 entries pointing at them.
+  pub fn remove_message(&mut self, label: &str) -> Result<Vec<txt2::Token>> {
+    self.edit(|session| session.remove(label))?
+  }
+
+  /// Rename the label `old` to `new` without changing its value, moving it to the group `new` hashes to.
+  ///
+  /// Fails if there is no label `old`, if there already is one called `new`, or if `new` is longer than 255
+  /// bytes.
+  pub fn rename_label(&mut self, old: &str, new: &str) -> Result<()> {
+    self.edit(|session| session.rename(old, new))?
+  }
+
+  /// Replace the values of existing messages by label, returning the labels that were not found.
+  pub fn apply_overrides(&mut self, overrides: &BTreeMap<String, Vec<txt2::Token>>) -> Result<Vec<String>> {
+    self.edit(|session| {
+      let mut missing = Vec::new();
+      for (label, value) in overrides {
+        if session.set(label, value.clone()).is_err() {
+          missing.push(label.clone());
+        }
+      }
+      missing
+    })
+  }
+
+  /// The size of the given section when written, including its alignment padding, or `None` if it is not
+  /// written because it is missing or not in `section_order`.
+  pub fn section_size(&self, tag: &SectionTag) -> Option<usize> {
+    self.layout().into_iter()
+      .find(|(t, _, _)| *t == tag)
+      .map(|(_, start, size)| size + padding_len(start + size as u64, self.alignment) as usize)
+  }
+
+  /// Like `section_size`, but without the alignment padding.
+  pub(crate) fn unpadded_section_size(&self, tag: &SectionTag) -> Option<usize> {
+    match *tag {
+      SectionTag::Lbl1 => self.lbl1.as_ref().map(CalculatesSize::calc_size),
+      SectionTag::Nli1 => self.nli1.as_ref().map(CalculatesSize::calc_size),
+      SectionTag::Ato1 => self.ato1.as_ref().map(CalculatesSize::calc_size),
+      SectionTag::Atr1 => self.atr1.as_ref().map(CalculatesSize::calc_size),
+      SectionTag::Tsy1 => self.tsy1.as_ref().map(CalculatesSize::calc_size),
+      SectionTag::Txt2 => self.txt2.as_ref().map(CalculatesSize::calc_size),
+      SectionTag::Unknown(magic) => self.unknown_sections.iter()
+        .find(|section| section.section.magic == magic)
+        .map(CalculatesSize::calc_size),
+    }
+  }
+
+  /// The size of the whole file when written.
+  pub fn total_size(&self) -> usize {
+    self.calc_size()
+  }
+
+  /// Re-encode all text in this file into `encoding`, updating the header to match.
+  pub fn convert_encoding(&mut self, encoding: Encoding) -> Result<()> {
+    if let Some(txt2) = self.txt2.as_mut() {
+      txt2.convert_encoding(encoding)?;
+    }
+    self.header.encoding = encoding;
+
+    Ok(())
+  }
+
+  /// Change the byte order this file is written in.
+  ///
+  /// TXT2 text is converted, as are the offsets in ATO1, the style indices in TSY1 and the entry count and
+  /// attribute size in ATR1. ATR1 entries are opaque, so their bytes are left untouched.
+  pub fn set_endianness(&mut self, endianness: Endianness) {
+    if endianness != self.header.endianness {
+      if let Some(ato1) = self.ato1.as_mut() {
+        ato1.swap_byte_order();
+      }
+      if let Some(atr1) = self.atr1.as_mut() {
+        atr1.swap_byte_order();
+      }
+      if let Some(tsy1) = self.tsy1.as_mut() {
+        tsy1.swap_byte_order();
+      }
+    }
+    if let Some(txt2) = self.txt2.as_mut() {
+      txt2.set_endianness(endianness);
+    }
+    self.header.set_endianness(endianness);
+  }
+
+  /// Check that the file size stored in the header matches the size this file would be written as.
+  ///
+  /// Files whose header has no size, as some tools write, always pass.
+  pub fn verify_size(&self) -> Result<()> {
+    if !self.header.has_declared_size() {
+      return Ok(());
+    }
+    let stored = self.header.declared_file_size;
+    let computed = self.calc_size() as u32;
+    if stored != computed {
+      return Err(Error::SizeMismatch { stored, computed });
+    }
+    Ok(())
+  }
+
+  /// Recompute every stored value that is derived from the contents: section sizes, LBL1 groups, the
+  /// NLI1 id count, and the header's section count and file size.
+  ///
+  /// The TXT2 offsets read from a file are kept, since offsets are always recomputed when writing.
+  /// Calling this twice in a row changes nothing the second time.
+  pub fn normalize(&mut self) {
+    self.update();
+    if let Some(lbl1) = self.lbl1.as_mut() {
+      lbl1.update();
+    }
+    if let Some(nli1) = self.nli1.as_mut() {
+      nli1.update();
+    }
+    if let Some(txt2) = self.txt2.as_mut() {
+      txt2.update();
+    }
+    if let Some(ato1) = self.ato1.as_mut() {
+      ato1.section.size = ato1._unknown.len() as u32;
+    }
+    if let Some(atr1) = self.atr1.as_mut() {
+      atr1.section.size = atr1._unknown.len() as u32;
+    }
+    if let Some(tsy1) = self.tsy1.as_mut() {
+      tsy1.section.size = tsy1._unknown.len() as u32;
+    }
+    for section in &mut self.unknown_sections {
+      section.section.size = section.data.len() as u32;
+    }
+    self.header.declared_file_size = self.calc_size() as u32;
+  }
+
+  /// Each section in `section_order` with the offset it starts at and its size without padding, laid out
+  /// the way `write_to` writes them.
+  fn layout(&self) -> Vec<(&SectionTag, u64, usize)> {
+    let mut pos = self.header.calc_file_size() as u64;
+    let mut unknown = self.unknown_sections.iter();
+    let mut layout = Vec::with_capacity(self.section_order.len());
+    for tag in &self.section_order {
+      let size = match tag {
+        SectionTag::Unknown(_) => unknown.next().map(CalculatesSize::calc_size),
+        tag => self.unpadded_section_size(tag),
+      };
+      if let Some(size) = size {
+        layout.push((tag, pos, size));
+        pos += size as u64;
+        pos += padding_len(pos, self.alignment);
+      }
+    }
+    layout
+  }
+}
+
+/// An empty little-endian UTF-16 file with LBL1 and TXT2, ready for `insert_message`.
+impl Default for Msbt {
+  fn default() -> Self {
+    MsbtBuilder::new(Endianness::Little, Encoding::Utf16, Some(builder::DEFAULT_GROUP_COUNT)).build()
+      .expect("a file with no messages always builds")
   }
 }
 
 impl CalculatesSize for Msbt {
   fn calc_size(&self) -> usize {
-    self.header.calc_file_size()
-      + Msbt::plus_padding(self.lbl1.as_ref().map(|x| x.calc_size()).unwrap_or(0))
-      + Msbt::plus_padding(self.nli1.as_ref().map(CalculatesSize::calc_size).unwrap_or(0))
-      + Msbt::plus_padding(self.ato1.as_ref().map(CalculatesSize::calc_size).unwrap_or(0))
-      + Msbt::plus_padding(self.atr1.as_ref().map(CalculatesSize::calc_size).unwrap_or(0))
-      + Msbt::plus_padding(self.tsy1.as_ref().map(CalculatesSize::calc_size).unwrap_or(0))
-      + Msbt::plus_padding(self.txt2.as_ref().map(CalculatesSize::calc_size).unwrap_or(0))
+    match self.layout().last() {
+      Some(&(_, start, size)) => {
+        let end = start + size as u64;
+        (end + padding_len(end, self.alignment)) as usize
+      },
+      None => self.header.calc_file_size(),
+    }
   }
 }
 
@@ -159,21 +817,39 @@ impl Updates for Msbt {
   }
 }
 
+/// The number of padding bytes needed after `pos` to reach a multiple of `alignment`.
+///
+/// Padding is always relative to the start of the file, which both the writer and `calc_size` use.
+fn padding_len(pos: u64, alignment: usize) -> u64 {
+  match alignment as u64 {
+    0 | 1 => 0,
+    alignment => (alignment - pos % alignment) % alignment,
+  }
+}
+
 #[derive(Debug)]
 pub struct MsbtWriter<'a, W> {
   writer: Counter<W>,
   msbt: &'a Msbt,
+  sections_written: usize,
 }
 
 impl<'a, W: Write> MsbtWriter<'a, W> {
-  fn new(msbt: &'a Msbt, writer: W) -> Self {
+  pub fn new(msbt: &'a Msbt, writer: W) -> Self {
     MsbtWriter {
       msbt,
       writer: Counter::new(writer),
+      sections_written: 0,
     }
   }
 
-  fn write_header(&mut self) -> Result<()> {
+  /// The underlying writer and the number of bytes written to it.
+  pub fn into_inner(self) -> (W, usize) {
+    let written = self.writer.written();
+    (self.writer.into_inner(), written)
+  }
+
+  pub fn write_header(&mut self) -> Result<()> {
     self.writer.write_all(&self.msbt.header.magic).map_err(Error::Io)?;
     let endianness = match self.msbt.header.endianness {
       Endianness::Big => [0xFE, 0xFF],
@@ -181,17 +857,22 @@ impl<'a, W: Write> MsbtWriter<'a, W> {
     };
     self.writer.write_all(&endianness).map_err(Error::Io)?;
     self.msbt.header.endianness.write_u16(&mut self.writer, self.msbt.header._unknown_1).map_err(Error::Io)?;
-    let encoding_byte = self.msbt.header.encoding as u8;
+    let encoding_byte = u8::from(self.msbt.header.encoding);
     self.writer.write_all(&[encoding_byte, self.msbt.header._unknown_2]).map_err(Error::Io)?;
-    self.msbt.header.endianness.write_u16(&mut self.writer, self.msbt.header.section_count).map_err(Error::Io)?;
+    // always derive the count from the sections that will actually be written
+    let section_count = self.msbt.section_order.len() as u16;
+    self.msbt.header.endianness.write_u16(&mut self.writer, section_count).map_err(Error::Io)?;
     self.msbt.header.endianness.write_u16(&mut self.writer, self.msbt.header._unknown_3).map_err(Error::Io)?;
-    self.msbt.header.endianness.write_u32(&mut self.writer, self.msbt.calc_size() as u32).map_err(Error::Io)?;
+    let file_size = self.msbt.declared_size_override.unwrap_or(self.msbt.calc_size() as u32);
+    self.msbt.header.endianness.write_u32(&mut self.writer, file_size).map_err(Error::Io)?;
     self.writer.write_all(&self.msbt.header.padding).map_err(Error::Io)
   }
 
-  fn write_section(&mut self, section: &Section) -> Result<()> {
+  /// Write `section`, the header of `content`, with the size `content` has now rather than the stored one.
+  fn write_section<S: CalculatesSize>(&mut self, section: &Section, content: &S) -> Result<()> {
+    let size = (content.calc_size() - section.calc_size()) as u32;
     self.writer.write_all(&section.magic).map_err(Error::Io)?;
-    self.msbt.header.endianness.write_u32(&mut self.writer, section.size).map_err(Error::Io)?;
+    self.msbt.header.endianness.write_u32(&mut self.writer, size).map_err(Error::Io)?;
     self.writer.write_all(&section.padding).map_err(Error::Io)
   }
 
@@ -200,19 +881,26 @@ impl<'a, W: Write> MsbtWriter<'a, W> {
     self.msbt.header.endianness.write_u32(&mut self.writer, group.offset).map_err(Error::Io)
   }
 
-  fn write_lbl1(&mut self) -> Result<()> {
+  pub fn write_lbl1(&mut self) -> Result<()> {
     if let Some(ref lbl1) = self.msbt.lbl1 {
-      self.write_section(&lbl1.section)?;
+      // names can be changed through `Label::set_name`, so check they fit before writing anything
+      for label in &lbl1.labels {
+        Label::check_name(label.name_bytes())?;
+      }
+      self.write_section(&lbl1.section, lbl1)?;
+      if lbl1.is_bare() {
+        return self.write_padding();
+      }
       self.msbt.header.endianness.write_u32(&mut self.writer, lbl1.groups().len() as u32).map_err(Error::Io)?;
-      for group in &lbl1.groups {
+      // groups are always derived from the labels, so stale counts and offsets are never written
+      for group in &lbl1.computed_groups() {
         self.write_group(group)?;
       }
-      let mut sorted_labels: Vec<(usize, &Label)> = lbl1.labels.iter().enumerate().collect();
-      sorted_labels.sort_by_key(|(_,l)| l.checksum(lbl1));
-      for (i, label) in &sorted_labels {
-        self.writer.write_all(&[label.name.len() as u8]).map_err(Error::Io)?;
-        self.writer.write_all(label.name.as_bytes()).map_err(Error::Io)?;
-        self.msbt.header.endianness.write_u32(&mut self.writer, *i as u32).map_err(Error::Io)?;
+      for i in lbl1.write_order() {
+        let label = &lbl1.labels[i];
+        self.writer.write_all(&[label.name_bytes().len() as u8]).map_err(Error::Io)?;
+        self.writer.write_all(label.name_bytes()).map_err(Error::Io)?;
+        self.msbt.header.endianness.write_u32(&mut self.writer, i as u32).map_err(Error::Io)?;
       }
 
       self.write_padding()?;
@@ -222,10 +910,10 @@ impl<'a, W: Write> MsbtWriter<'a, W> {
 
   pub fn write_nli1(&mut self) -> Result<()> {
     if let Some(ref nli1) = self.msbt.nli1 {
-      self.write_section(&nli1.section)?;
+      self.write_section(&nli1.section, nli1)?;
 
-      if nli1.section.size > 0 {
-        self.msbt.header.endianness.write_u32(&mut self.writer, nli1.id_count).map_err(Error::Io)?;
+      if !nli1.is_bare() {
+        self.msbt.header.endianness.write_u32(&mut self.writer, nli1.global_ids.len() as u32).map_err(Error::Io)?;
 
         for (&key, &val) in &nli1.global_ids {
           self.msbt.header.endianness.write_u32(&mut self.writer, val).map_err(Error::Io)?;
@@ -241,25 +929,25 @@ impl<'a, W: Write> MsbtWriter<'a, W> {
 
   pub fn write_txt2(&mut self) -> Result<()> {
     if let Some(ref txt2) = self.msbt.txt2 {
-      self.write_section(&txt2.section)?;
+      self.write_section(&txt2.section, txt2)?;
+      if txt2.is_bare() {
+        return self.write_padding();
+      }
 
       // write string count
       let value_count = txt2.values.len() as u32;
       self.msbt.header.endianness.write_u32(&mut self.writer, value_count).map_err(Error::Io)?;
 
+      let values_bytes: Vec<Vec<u8>> = txt2.raw_entries().collect();
+
       // write offsets
-      let mut total = 0;
-      for s in &txt2.values {
-        let offset = value_count * 4 + 4 + total;
-        total += s.len() as u32;
+      for offset in txt2.computed_offsets() {
         self.msbt.header.endianness.write_u32(&mut self.writer, offset).map_err(Error::Io)?;
       }
 
       // write strings
-      for s in &txt2.values {
-        let value_bytes = s.iter()
-          .flat_map(|vv| vv.to_bytes()).collect::<Vec<u8>>();
-        self.writer.write_all(&value_bytes).map_err(Error::Io)?;
+      for value_bytes in &values_bytes {
+        self.writer.write_all(value_bytes).map_err(Error::Io)?;
       }
 
       self.write_padding()?;
@@ -270,7 +958,7 @@ impl<'a, W: Write> MsbtWriter<'a, W> {
 
   pub fn write_ato1(&mut self) -> Result<()> {
     if let Some(ref ato1) = self.msbt.ato1 {
-      self.write_section(&ato1.section)?;
+      self.write_section(&ato1.section, ato1)?;
       self.writer.write_all(&ato1._unknown).map_err(Error::Io)?;
 
       self.write_padding()?;
@@ -281,7 +969,7 @@ impl<'a, W: Write> MsbtWriter<'a, W> {
 
   pub fn write_atr1(&mut self) -> Result<()> {
     if let Some(ref atr1) = self.msbt.atr1 {
-      self.write_section(&atr1.section)?;
+      self.write_section(&atr1.section, atr1)?;
       self.writer.write_all(&atr1._unknown).map_err(Error::Io)?;
 
       self.write_padding()?;
@@ -292,7 +980,7 @@ impl<'a, W: Write> MsbtWriter<'a, W> {
 
   pub fn write_tsy1(&mut self) -> Result<()> {
     if let Some(ref tsy1) = self.msbt.tsy1 {
-      self.write_section(&tsy1.section)?;
+      self.write_section(&tsy1.section, tsy1)?;
       self.writer.write_all(&tsy1._unknown).map_err(Error::Io)?;
 
       self.write_padding()?;
@@ -301,28 +989,68 @@ impl<'a, W: Write> MsbtWriter<'a, W> {
     Ok(())
   }
 
+  pub fn write_unknown(&mut self, section: &UnknownSection) -> Result<()> {
+    self.write_section(&section.section, section)?;
+    self.writer.write_all(&section.data).map_err(Error::Io)?;
+
+    self.write_padding()
+  }
+
   fn write_padding(&mut self) -> Result<()> {
-    let remainder = self.writer.written() % PADDING_LENGTH;
-    if remainder == 0 {
-      return Ok(());
+    let mut remaining = padding_len(self.writer.written() as u64, self.msbt.alignment) as usize;
+    let index = self.sections_written;
+    self.sections_written += 1;
+    if let Some(bytes) = self.msbt.padding_bytes.get(index).filter(|bytes| bytes.len() == remaining) {
+      return self.writer.write_all(bytes).map_err(Error::Io);
     }
 
-    self.writer.write_all(&vec![self.msbt.pad_byte; PADDING_LENGTH - remainder]).map_err(Error::Io)
+    let padding = [self.msbt.pad_byte; DEFAULT_ALIGNMENT];
+    while remaining > 0 {
+      let len = remaining.min(padding.len());
+      self.writer.write_all(&padding[..len]).map_err(Error::Io)?;
+      remaining -= len;
+    }
+    Ok(())
   }
 }
 
+/// A reader that can parse an MSBT one section at a time.
+///
+/// `new` reads the header and leaves the stream at the start of the first section. Each `read_*` method
+/// expects the stream to be at the start of its section and leaves it at the end of that section's data,
+/// so `skip_padding` must be called before reading the next one. `read_sections` does all of this and
+/// stores every section in the `Msbt` returned by `into_msbt`.
 #[derive(Debug)]
 pub struct MsbtReader<R> {
   reader: R,
+  options: ReaderOptions,
+  stream_len: u64,
   msbt: Msbt,
 }
 
-impl<'a, R: Read + Seek> MsbtReader<R> {
-  fn new(mut reader: R) -> Result<Self> {
-    let header = Header::from_reader(&mut reader)?;
+impl<R: Read + Seek> MsbtReader<R> {
+  pub fn new(reader: R) -> Result<Self> {
+    MsbtReader::with_options(reader, ReaderOptions::default())
+  }
 
-    let mut msbt = MsbtReader {
+  pub fn with_options(mut reader: R, options: ReaderOptions) -> Result<Self> {
+    let start = reader.stream_position().map_err(Error::Io)?;
+    let header = Header::from_reader(&mut reader).map_err(|e| match e {
+      Error::InvalidMagic { offset } => Error::InvalidMagic { offset: start + offset },
+      e => e,
+    })?;
+
+    let pos = reader.stream_position().map_err(Error::Io)?;
+    let stream_len = reader.seek(SeekFrom::End(0)).map_err(Error::Io)?;
+    reader.seek(SeekFrom::Start(pos)).map_err(Error::Io)?;
+    let alignment = options.alignment.unwrap_or(DEFAULT_ALIGNMENT);
+    // every section has a 16-byte header, which bounds the hint for files that lie about their count
+    let section_count = u64::from(header.section_count).min(stream_len / 16) as usize;
+
+    Ok(MsbtReader {
       reader,
+      options,
+      stream_len,
       msbt: Msbt{
         header,
         lbl1: None,
@@ -331,38 +1059,127 @@ impl<'a, R: Read + Seek> MsbtReader<R> {
         atr1: None,
         tsy1: None,
         txt2: None,
-        section_order: Vec::with_capacity(6),
+        unknown_sections: Vec::new(),
+        section_order: Vec::with_capacity(section_count),
         pad_byte: 0,
+        alignment,
+        declared_size_override: None,
+        padding_bytes: Vec::new(),
       }
-    };
+    })
+  }
+
+  pub fn header(&self) -> &Header {
+    &self.msbt.header
+  }
+
+  /// The `Msbt` containing the header and every section read by `read_sections`.
+  pub fn into_msbt(self) -> Msbt {
+    self.msbt
+  }
+
+  /// Look at the magic of the next section without moving the stream, or `None` at the end of the stream.
+  pub fn peek_magic(&mut self) -> Result<Option<[u8; 4]>> {
+    let mut peek = [0; 4];
+    match self.reader.read_exact(&mut peek) {
+      Ok(()) => {},
+      Err(ref e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+      Err(e) => return Err(Error::Io(e)),
+    }
+
+    self.reader.seek(SeekFrom::Current(-4)).map_err(Error::Io)?;
 
-    msbt.read_sections()?;
+    Ok(Some(peek))
+  }
+
+  /// Skip over the next section and its padding without decoding it.
+  pub fn skip_section(&mut self) -> Result<()> {
+    let section = self.read_section()?;
+    self.reader.seek(SeekFrom::Current(i64::from(section.size))).map_err(Error::Io)?;
+    self.skip_padding()
+  }
 
-    Ok(msbt)
+  /// Make sure `count` entries of at least `entry_size` bytes each can fit in the rest of the stream.
+  fn check_remaining(&mut self, count: u64, entry_size: u64) -> Result<()> {
+    let pos = self.reader.stream_position().map_err(Error::Io)?;
+    let remaining = self.stream_len.saturating_sub(pos);
+    if count.saturating_mul(entry_size) > remaining {
+      return Err(Error::TooManyEntries { count, remaining });
+    }
+    Ok(())
   }
 
-  fn skip_padding(&mut self) -> Result<()> {
+  /// Make sure the data of `section` fits in the rest of the stream before allocating room for it.
+  fn check_section_size(&mut self, section: &Section) -> Result<()> {
     let pos = self.reader.stream_position().map_err(Error::Io)?;
-    let remainder = pos % PADDING_LENGTH as u64;
-    if remainder > 0 {
-      let mut buf = [0; 1];
+    if u64::from(section.size) > self.stream_len.saturating_sub(pos) {
+      return Err(Error::SectionTooLarge { magic: section.magic, size: section.size });
+    }
+    Ok(())
+  }
+
+  /// Move the stream past the alignment padding after a section.
+  pub fn skip_padding(&mut self) -> Result<()> {
+    let pos = self.reader.stream_position().map_err(Error::Io)?;
+    let len = padding_len(pos, self.msbt.alignment);
+    if len > 0 {
+      let end = pos + len;
+      if end > self.stream_len && self.options.strict_padding {
+        return Err(Error::Io(std::io::ErrorKind::UnexpectedEof.into()));
+      }
+
+      // some dumps are missing the padding at the end of the file
+      let available = end.min(self.stream_len).saturating_sub(pos);
+      if available == 0 {
+        return Ok(());
+      }
+      let mut buf = vec![0; available as usize];
       self.reader.read_exact(&mut buf).map_err(Error::Io)?;
-      self.reader.seek(SeekFrom::Start(pos + PADDING_LENGTH as u64 - remainder)).map_err(Error::Io)?;
+      self.reader.seek(SeekFrom::Start(end)).map_err(Error::Io)?;
       self.msbt.pad_byte = buf[0];
+
+      if self.options.strict_padding {
+        if let Some(index) = self.msbt.section_order.len().checked_sub(1) {
+          self.msbt.padding_bytes.resize(index + 1, Vec::new());
+          self.msbt.padding_bytes[index] = buf;
+        }
+      }
     }
     Ok(())
   }
 
   pub fn read_sections(&mut self) -> Result<()> {
-    let mut peek = [0; 4];
+    self.read_all_sections(false).map(|_| ())
+  }
+
+  /// Like `read_sections`, but TXT2 is left undecoded and returned instead of stored in the `Msbt`.
+  pub fn read_sections_lazy(&mut self) -> Result<Option<LazyTxt2>> {
+    self.read_all_sections(true)
+  }
+
+  fn read_all_sections(&mut self, lazy_txt2: bool) -> Result<Option<LazyTxt2>> {
+    let mut lazy = None;
     loop {
-      match self.reader.read_exact(&mut peek) {
-        Ok(()) => {},
-        Err(ref e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(()),
-        Err(e) => return Err(Error::Io(e)),
-      }
+      let offset = self.reader.stream_position().map_err(Error::Io)?;
+      let peek = match self.peek_magic()? {
+        Some(peek) => peek,
+        None => return Ok(lazy),
+      };
 
-      self.reader.seek(SeekFrom::Current(-4)).map_err(Error::Io)?;
+      // a second copy of a known section would replace the first and be written twice
+      let known = match &peek {
+        b"LBL1" => Some(SectionTag::Lbl1),
+        b"NLI1" => Some(SectionTag::Nli1),
+        b"ATO1" => Some(SectionTag::Ato1),
+        b"ATR1" => Some(SectionTag::Atr1),
+        b"TSY1" => Some(SectionTag::Tsy1),
+        b"TXT2" => Some(SectionTag::Txt2),
+        _ => None,
+      };
+      let seen = |tag: &SectionTag| self.msbt.has_section(tag) || (tag == &SectionTag::Txt2 && lazy.is_some());
+      if let Some(tag) = known.filter(seen) {
+        return Err(Error::DuplicateSection { tag, offset });
+      }
 
       match &peek {
         b"LBL1" => {
@@ -382,14 +1199,23 @@ impl<'a, R: Read + Seek> MsbtReader<R> {
           self.msbt.section_order.push(SectionTag::Tsy1);
         },
         b"TXT2" => {
-          self.msbt.txt2 = Some(self.read_txt2()?);
+          if lazy_txt2 {
+            lazy = Some(self.read_txt2_lazy()?);
+          } else {
+            self.msbt.txt2 = Some(self.read_txt2()?);
+          }
           self.msbt.section_order.push(SectionTag::Txt2);
         },
         b"NLI1" => {
           self.msbt.nli1 = Some(self.read_nli1()?);
           self.msbt.section_order.push(SectionTag::Nli1);
         },
-        _ => return Err(Error::InvalidSection(peek)),
+        _ if self.options.keep_unknown_sections => {
+          let section = self.read_unknown()?;
+          self.msbt.unknown_sections.push(section);
+          self.msbt.section_order.push(SectionTag::Unknown(peek));
+        },
+        _ => return Err(Error::InvalidSection { magic: peek, offset }),
       }
 
       self.skip_padding()?;
@@ -397,20 +1223,38 @@ impl<'a, R: Read + Seek> MsbtReader<R> {
   }
 
   pub fn read_lbl1(&mut self) -> Result<Lbl1> {
+    let offset = self.reader.stream_position().map_err(Error::Io)?;
     let section = self.read_section()?;
 
     if &section.magic != b"LBL1" {
-      return Err(Error::InvalidMagic);
+      return Err(Error::InvalidMagic { offset });
+    }
+    if section.size == 0 {
+      return Ok(Lbl1 {
+        section,
+        groups: Vec::new(),
+        labels: Vec::new(),
+        read_order: Vec::new(),
+        preserve_order: false,
+      });
     }
 
     let group_count = self.msbt.header.endianness.read_u32(&mut self.reader).map_err(Error::Io)?;
+    self.check_remaining(u64::from(group_count), 8)?;
     let mut groups = Vec::with_capacity(group_count as usize);
     for _ in 0..group_count {
       groups.push(self.read_group()?);
     }
 
-    let label_count = groups.iter().map(|x| x.label_count as usize).sum();
-    let mut labels = vec![Label{name: "".to_string()}; label_count];
+    let label_count = groups.iter().map(|x| u64::from(x.label_count)).sum::<u64>();
+    // each label is at least a length byte and an index
+    self.check_remaining(label_count, 5)?;
+    let label_count = label_count as usize;
+    let mut labels = vec![Label::new(String::new()); label_count];
+
+    let mut read_order = Vec::with_capacity(label_count);
+    let mut seen_names = BTreeSet::new();
+    let mut seen_indices = BTreeSet::new();
 
     let mut buf = [0; 1];
     for group in groups.iter() {
@@ -418,10 +1262,36 @@ impl<'a, R: Read + Seek> MsbtReader<R> {
         self.reader.read_exact(&mut buf).map_err(Error::Io)?;
         let str_len = buf[0] as usize;
         let mut str_buf = vec![0; str_len];
+        let offset = self.reader.stream_position().map_err(Error::Io)?;
         self.reader.read_exact(&mut str_buf).map_err(Error::Io)?;
-        let name = String::from_utf8(str_buf).map_err(Error::InvalidUtf8)?;
+        let label = match String::from_utf8(str_buf) {
+          Ok(name) => Label::new(name),
+          Err(e) if self.options.lossy_label_names => Label {
+            name: String::from_utf8_lossy(e.as_bytes()).into_owned(),
+            raw_name: Some(e.into_bytes()),
+          },
+          Err(source) => return Err(Error::InvalidUtf8 { offset, source }),
+        };
         let index = self.msbt.header.endianness.read_u32(&mut self.reader).map_err(Error::Io)?;
-        labels[index as usize] = Label{ name };
+        if self.options.reject_duplicate_labels {
+          if !seen_indices.insert(index) {
+            return Err(Error::DuplicateLabelIndex(index));
+          }
+          if !seen_names.insert(label.name.clone()) {
+            return Err(Error::DuplicateLabel(label.name));
+          }
+        }
+        let index_fits = (index as usize) < labels.len()
+          // every TXT2 value has a four-byte offset, so no real index can be larger than this
+          || self.options.grow_labels && u64::from(index) < self.stream_len / 4;
+        if !index_fits {
+          return Err(Error::LabelIndexOutOfRange { index, count: label_count });
+        }
+        if labels.len() <= index as usize {
+          labels.resize(index as usize + 1, Label::new(String::new()));
+        }
+        labels[index as usize] = label;
+        read_order.push(index);
       }
     }
 
@@ -429,6 +1299,8 @@ impl<'a, R: Read + Seek> MsbtReader<R> {
       section,
       groups,
       labels,
+      read_order,
+      preserve_order: false,
     };
 
     Ok(lbl1)
@@ -436,6 +1308,7 @@ impl<'a, R: Read + Seek> MsbtReader<R> {
 
   pub fn read_atr1(&mut self) -> Result<Atr1> {
     let section = self.read_section()?;
+    self.check_section_size(&section)?;
     let mut unknown = vec![0; section.size as usize];
     self.reader.read_exact(&mut unknown).map_err(Error::Io)?;
 
@@ -447,6 +1320,7 @@ impl<'a, R: Read + Seek> MsbtReader<R> {
 
   pub fn read_ato1(&mut self) -> Result<Ato1> {
     let section = self.read_section()?;
+    self.check_section_size(&section)?;
     let mut unknown = vec![0; section.size as usize];
     self.reader.read_exact(&mut unknown).map_err(Error::Io)?;
 
@@ -458,6 +1332,7 @@ impl<'a, R: Read + Seek> MsbtReader<R> {
 
   pub fn read_tsy1(&mut self) -> Result<Tsy1> {
     let section = self.read_section()?;
+    self.check_section_size(&section)?;
     let mut unknown = vec![0; section.size as usize];
     self.reader.read_exact(&mut unknown).map_err(Error::Io)?;
 
@@ -467,32 +1342,71 @@ impl<'a, R: Read + Seek> MsbtReader<R> {
     })
   }
 
+  pub fn read_unknown(&mut self) -> Result<UnknownSection> {
+    let section = self.read_section()?;
+    self.check_section_size(&section)?;
+    let mut data = vec![0; section.size as usize];
+    self.reader.read_exact(&mut data).map_err(Error::Io)?;
+
+    Ok(UnknownSection {
+      section,
+      data,
+    })
+  }
+
   pub fn read_txt2(&mut self) -> Result<Txt2> {
+    self.read_txt2_lazy().map(LazyTxt2::into_txt2)
+  }
+
+  /// Read TXT2 without decoding any of its values.
+  pub fn read_txt2_lazy(&mut self) -> Result<LazyTxt2> {
     let section = self.read_section()?;
-    let string_count = self.msbt.header.endianness.read_u32(&mut self.reader).map_err(Error::Io)? as usize;
+    self.check_section_size(&section)?;
+    let mut data = vec![0; section.size as usize];
+    self.reader.read_exact(&mut data).map_err(Error::Io)?;
+    if data.is_empty() {
+      return Ok(LazyTxt2 {
+        section,
+        data,
+        offsets: Vec::new(),
+        encoding: self.msbt.header.encoding,
+        endianness: self.msbt.header.endianness,
+      });
+    }
 
-    let mut offsets = Vec::with_capacity(string_count);
-    let mut values = Vec::with_capacity(string_count);
+    let mut table = &data[..];
+    let string_count = self.msbt.header.endianness.read_u32(&mut table).map_err(Error::Io)? as usize;
+    if string_count as u64 * 4 > table.len() as u64 {
+      return Err(Error::TooManyEntries { count: string_count as u64, remaining: table.len() as u64 });
+    }
 
+    let mut offsets = Vec::with_capacity(string_count);
     for _ in 0..string_count {
-      offsets.push(self.msbt.header.endianness.read_u32(&mut self.reader).map_err(Error::Io)?);
+      offsets.push(self.msbt.header.endianness.read_u32(&mut table).map_err(Error::Io)?);
     }
 
+    // strings must start after the offset table and not run past the end of the section
+    let table_end = (std::mem::size_of::<u32>() * (string_count + 1)) as u64;
     for i in 0..string_count {
       let next_str_end = if i == string_count - 1 {
         section.size
       } else {
         offsets[i + 1]
       };
-      let str_len = next_str_end - offsets[i];
-      let mut str_buf = vec![0; str_len as usize];
-      self.reader.read_exact(&mut str_buf).map_err(Error::Io)?;
-      values.push(txt2::parse_bytes(&str_buf));
+      if u64::from(offsets[i]) < table_end || offsets[i] > section.size {
+        return Err(Error::InvalidTxt2Offset(offsets[i]));
+      }
+      if next_str_end < offsets[i] || next_str_end > section.size {
+        return Err(Error::InvalidTxt2Offset(next_str_end));
+      }
     }
 
-    Ok(Txt2 {
+    Ok(LazyTxt2 {
       section,
-      values,
+      data,
+      offsets,
+      encoding: self.msbt.header.encoding,
+      endianness: self.msbt.header.endianness,
     })
   }
 
@@ -504,6 +1418,7 @@ impl<'a, R: Read + Seek> MsbtReader<R> {
 
     if section.size > 0 {
       id_count = self.msbt.header.endianness.read_u32(&mut self.reader).map_err(Error::Io)?;
+      self.check_remaining(u64::from(id_count), 8)?;
 
       for _ in 0..id_count {
         let val = self.msbt.header.endianness.read_u32(&mut self.reader).map_err(Error::Io)?;
@@ -512,11 +1427,7 @@ impl<'a, R: Read + Seek> MsbtReader<R> {
       }
     }
 
-    Ok(Nli1 {
-      section,
-      id_count,
-      global_ids: map,
-    })
+    Ok(Nli1::with_section(section, id_count, map))
   }
 
   pub fn read_group(&mut self) -> Result<Group> {
@@ -537,37 +1448,52 @@ impl<'a, R: Read + Seek> MsbtReader<R> {
     let size = self.msbt.header.endianness.read_u32(&mut self.reader).map_err(Error::Io)?;
     self.reader.read_exact(&mut padding).map_err(Error::Io)?;
 
-    Ok(Section {
-      magic,
-      size,
-      padding,
-    })
+    Ok(Section::with_padding(magic, size, padding))
   }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
 pub struct Header {
   pub(crate) magic: [u8; 8],
+  #[cfg_attr(feature = "serde_support", serde(with = "EndiannessDef"))]
   pub(crate) endianness: Endianness,
   pub(crate) _unknown_1: u16,
   pub(crate) encoding: Encoding,
   pub(crate) _unknown_2: u8,
   pub(crate) section_count: u16,
   pub(crate) _unknown_3: u16,
+  pub(crate) declared_file_size: u32,
   pub(crate) padding: [u8; 10],
 }
 
 impl Header {
-  pub fn from_reader(mut reader: &mut dyn Read) -> Result<Self> {
-    let mut buf = [0u8; 10];
-    reader.read_exact(&mut buf[..8]).map_err(Error::Io)?;
+  pub fn new(endianness: Endianness, encoding: Encoding) -> Self {
+    Header {
+      magic: HEADER_MAGIC,
+      endianness,
+      _unknown_1: 0,
+      encoding,
+      _unknown_2: 3,
+      section_count: 0,
+      _unknown_3: 0,
+      declared_file_size: 0,
+      padding: [0; 10],
+    }
+  }
 
+  /// Read a header from `reader`, leaving it positioned at the first section. This does not need `Seek`.
+  ///
+  /// Without `Seek` the position in the stream is unknown, so the offset in `InvalidMagic` is relative to
+  /// where reading started. `MsbtReader` reports the position in the stream instead.
+  pub fn from_reader(mut reader: &mut dyn Read) -> Result<Self> {
     let mut magic = [0u8; 8];
-    magic.swap_with_slice(&mut buf[..8]);
+    reader.read_exact(&mut magic).map_err(Error::Io)?;
     if magic != HEADER_MAGIC {
-      return Err(Error::InvalidMagic);
+      return Err(Error::InvalidMagic { offset: 0 });
     }
 
+    let mut buf = [0u8; 10];
     reader.read_exact(&mut buf[..2]).map_err(Error::Io)?;
     let endianness = match buf[..2] {
       [0xFE, 0xFF] => Endianness::Big,
@@ -578,15 +1504,14 @@ impl Header {
     let unknown_1 = endianness.read_u16(&mut reader).map_err(Error::Io)?;
 
     reader.read_exact(&mut buf[..1]).map_err(Error::Io)?;
-    let encoding = Encoding::try_from(buf[0])
-      .map_err(|_| Error::InvalidEncoding(buf[0]))?;
+    let encoding = Encoding::from(buf[0]);
 
     reader.read_exact(&mut buf[..1]).map_err(Error::Io)?;
     let unknown_2 = buf[0];
 
     let section_count = endianness.read_u16(&mut reader).map_err(Error::Io)?;
     let unknown_3 = endianness.read_u16(&mut reader).map_err(Error::Io)?;
-    let _file_size = endianness.read_u32(&mut reader).map_err(Error::Io)?;
+    let declared_file_size = endianness.read_u32(&mut reader).map_err(Error::Io)?;
 
     reader.read_exact(&mut buf[..10]).map_err(Error::Io)?;
     let padding = buf;
@@ -596,6 +1521,7 @@ impl Header {
       endianness,
       encoding,
       section_count,
+      declared_file_size,
       padding,
       _unknown_1: unknown_1,
       _unknown_2: unknown_2,
@@ -607,14 +1533,27 @@ impl Header {
     self.magic
   }
 
+  /// The byte order, which also decides the byte order mark. No separate BOM is stored.
   pub fn endianness(&self) -> Endianness {
     self.endianness
   }
 
+  /// Set the byte order, and with it the byte order mark written.
+  ///
+  /// This only changes the header, so sections read with the old byte order are written unchanged. Use
+  /// `Msbt::set_endianness` to convert them too.
+  pub fn set_endianness(&mut self, endianness: Endianness) {
+    self.endianness = endianness;
+  }
+
   pub fn unknown_1(&self) -> u16 {
     self._unknown_1
   }
 
+  pub fn set_unknown_1(&mut self, unknown_1: u16) {
+    self._unknown_1 = unknown_1;
+  }
+
   pub fn encoding(&self) -> Encoding {
     self.encoding
   }
@@ -623,6 +1562,10 @@ impl Header {
     self._unknown_2
   }
 
+  pub fn set_unknown_2(&mut self, unknown_2: u8) {
+    self._unknown_2 = unknown_2;
+  }
+
   pub fn section_count(&self) -> u16 {
     self.section_count
   }
@@ -631,10 +1574,32 @@ impl Header {
     self._unknown_3
   }
 
+  pub fn set_unknown_3(&mut self, unknown_3: u16) {
+    self._unknown_3 = unknown_3;
+  }
+
+  /// The file size stored in the header when it was read, or the size at the time it was built for files
+  /// made with `MsbtBuilder`.
+  ///
+  /// This is never used when writing, which always stores the size of the contents being written unless
+  /// `Msbt::set_declared_size_override` is used.
+  pub fn declared_file_size(&self) -> u32 {
+    self.declared_file_size
+  }
+
+  /// Whether the header holds a file size. Some tools leave the field as zero, meaning the size is unknown.
+  pub fn has_declared_size(&self) -> bool {
+    self.declared_file_size != 0
+  }
+
   pub fn padding(&self) -> [u8; 10] {
     self.padding
   }
 
+  pub fn set_padding(&mut self, padding: [u8; 10]) {
+    self.padding = padding;
+  }
+
   pub(crate) fn calc_file_size(&self) -> usize {
     std::mem::size_of_val(&self.magic)
       + std::mem::size_of::<u16>() // endianness
@@ -648,20 +1613,146 @@ impl Header {
   }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
 pub enum Encoding {
-  Utf8 = 0x00,
-  Utf16 = 0x01,
+  Utf8,
+  Utf16,
+  /// An encoding byte this crate does not know how to decode (e.g. Shift-JIS in some older files).
+  ///
+  /// Files with such an encoding can still be read and written, but text is decoded as lossy UTF-8.
+  Other(u8),
 }
 
-impl std::convert::TryFrom<u8> for Encoding {
-  type Error = ();
+impl Encoding {
+  /// The size in bytes of one code unit: 2 for UTF-16 and 1 otherwise.
+  pub fn code_unit_size(&self) -> usize {
+    match self {
+      Encoding::Utf16 => 2,
+      Encoding::Utf8 | Encoding::Other(_) => 1,
+    }
+  }
 
-  fn try_from(value: u8) -> std::result::Result<Encoding, ()> {
-      Ok(match value {
-        0x00 => Encoding::Utf8,
-        0x01 => Encoding::Utf16,
-        _ => return Err(()),
-      })
+  /// The null terminator that ends each TXT2 value, one code unit of zeroes.
+  pub fn null_terminator(&self) -> &'static [u8] {
+    match self {
+      Encoding::Utf16 => &[0, 0],
+      Encoding::Utf8 | Encoding::Other(_) => &[0],
+    }
+  }
+}
+
+impl From<u8> for Encoding {
+  fn from(value: u8) -> Encoding {
+    match value {
+      0x00 => Encoding::Utf8,
+      0x01 => Encoding::Utf16,
+      x => Encoding::Other(x),
+    }
+  }
+}
+
+impl From<Encoding> for u8 {
+  fn from(encoding: Encoding) -> u8 {
+    match encoding {
+      Encoding::Utf8 => 0x00,
+      Encoding::Utf16 => 0x01,
+      Encoding::Other(x) => x,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const SAMPLE: &[u8] = include_bytes!("../tests/fixtures/sample.msbt");
+
+  #[test]
+  fn section_count_follows_section_order() {
+    let mut msbt = Msbt::parse_slice(SAMPLE).unwrap();
+    msbt.section_order.retain(|tag| tag != &SectionTag::Atr1);
+    assert_eq!(msbt.header.section_count, 3);
+
+    let bytes = msbt.to_vec().unwrap();
+    assert_eq!(&bytes[0x0E..0x10], &2u16.to_le_bytes());
+    let read = Msbt::parse_slice(&bytes).unwrap();
+    assert_eq!(read.header().section_count(), 2);
+    assert!(read.atr1().is_none());
+  }
+
+  #[test]
+  fn added_label_is_regrouped() {
+    let mut msbt = Msbt::parse_slice(SAMPLE).unwrap();
+    msbt.txt2_mut().unwrap().values.push(vec![txt2::Token::Padding(Vec::new())]);
+    {
+      let mut lbl1 = msbt.lbl1_mut().unwrap();
+      lbl1.labels.push(Label::new("Talk_003".to_string()));
+      lbl1.rebuild_groups(101).unwrap();
+    }
+
+    let read = Msbt::parse_slice(&msbt.to_vec().unwrap()).unwrap();
+    read.verify_size().unwrap();
+    let lbl1 = read.lbl1().unwrap();
+    assert_eq!(lbl1.index_of("Talk_003"), Some(4));
+    assert_eq!(lbl1.group_of(4), Some(Lbl1::hash("Talk_003", 101) as usize));
+    assert_eq!(lbl1.groups(), msbt.lbl1().unwrap().groups());
+    assert_eq!(lbl1.groups().iter().map(Group::label_count).sum::<u32>(), 5);
+  }
+
+  #[test]
+  fn stale_fields_are_recomputed_when_writing() {
+    let mut msbt = Msbt::parse_slice(SAMPLE).unwrap();
+    // bypass the updaters, so no stored size, count or offset changes
+    msbt.txt2.as_mut().unwrap().values.push(vec![txt2::Token::Padding(Vec::new())]);
+    msbt.lbl1.as_mut().unwrap().labels.push(Label::new("Talk_003".to_string()));
+    msbt.header.section_count = 7;
+
+    let bytes = msbt.to_vec().unwrap();
+    let read = Msbt::parse_slice(&bytes).unwrap();
+    read.verify_size().unwrap();
+    assert_eq!(read.header().section_count(), 3);
+    assert_eq!(read.header().declared_file_size() as usize, bytes.len());
+    assert_eq!(read.lbl1().unwrap().index_of("Talk_003"), Some(4));
+    assert_eq!(read.txt2().unwrap().values().len(), 5);
+  }
+
+  /// The sample with its last TXT2 value removed, so LBL1 has one label more than TXT2 has values.
+  fn sample_with_dangling_label() -> Msbt {
+    let mut msbt = Msbt::parse_slice(SAMPLE).unwrap();
+    msbt.txt2.as_mut().unwrap().values.pop();
+    msbt
+  }
+
+  #[test]
+  fn insert_does_not_shift_labels_past_the_last_value() {
+    let mut msbt = sample_with_dangling_label();
+    let value = vec![txt2::Token::Padding(Vec::new())];
+    assert!(matches!(msbt.insert_message("Talk_003", value.clone()), Err(Error::DuplicateLabelIndex(3))));
+    assert_eq!(msbt.txt2().unwrap().values().len(), 3);
+
+    msbt.lbl1.as_mut().unwrap().labels[3].set_name("");
+    assert_eq!(msbt.insert_message("Talk_003", value).unwrap(), 3);
+    let lbl1 = msbt.lbl1().unwrap();
+    assert_eq!(lbl1.labels().len(), 4);
+    assert_eq!(lbl1.index_of("Talk_001"), Some(0));
+    assert_eq!(lbl1.index_of("Sign_A"), Some(2));
+    assert_eq!(lbl1.index_of("Talk_003"), Some(3));
+  }
+
+  #[test]
+  fn nli1_insert_does_not_shift_labels_past_the_last_value() {
+    let mut msbt = sample_with_dangling_label();
+    let value = vec![txt2::Token::Padding(Vec::new())];
+    assert!(matches!(msbt.insert_nli1_message(7, value.clone()), Err(Error::DuplicateLabelIndex(3))));
+    assert_eq!(msbt.txt2().unwrap().values().len(), 3);
+    assert!(msbt.nli1().is_none());
+
+    msbt.lbl1.as_mut().unwrap().labels[3].set_name("");
+    assert_eq!(msbt.insert_nli1_message(7, value).unwrap(), 3);
+    let lbl1 = msbt.lbl1().unwrap();
+    assert_eq!(lbl1.labels().len(), 4);
+    assert_eq!(lbl1.index_of("Sign_A"), Some(2));
+    assert_eq!(lbl1.labels()[3].name(), "");
   }
 }
@@ -4,6 +4,7 @@ pub mod lbl1;
 pub mod nli1;
 pub mod tsy1;
 pub mod txt2;
+pub mod unknown;
 
 pub use self::{
   ato1::Ato1,
@@ -12,9 +13,14 @@ pub use self::{
   nli1::Nli1,
   tsy1::Tsy1,
   txt2::Txt2,
+  unknown::UnknownSection,
 };
 
-#[derive(Debug, Clone)]
+#[cfg(feature = "serde_support")]
+use serde_derive::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
 pub struct Section {
   pub magic: [u8; 4],
   pub size: u32,
@@ -23,12 +29,42 @@ pub struct Section {
 
 impl Section {
   pub fn new(magic: [u8; 4], size: u32) -> Self {
+    Section::with_padding(magic, size, [0; 8])
+  }
+
+  /// Create a section header with the given bytes after the size, instead of zeros.
+  pub fn with_padding(magic: [u8; 4], size: u32, padding: [u8; 8]) -> Self {
     Section {
       magic,
       size,
-      padding: [0; 8],
+      padding,
     }
   }
+
+  pub fn magic(&self) -> [u8; 4] {
+    self.magic
+  }
+
+  /// The size of the section's data, not including this header.
+  pub fn size(&self) -> u32 {
+    self.size
+  }
+
+  /// The 8 bytes following the size, which are usually zero.
+  pub fn padding(&self) -> [u8; 8] {
+    self.padding
+  }
+
+  pub fn set_padding(&mut self, padding: [u8; 8]) {
+    self.padding = padding;
+  }
+}
+
+/// Reverse the byte order of every whole u32 in `bytes`, leaving any trailing bytes as they are.
+pub(crate) fn swap_u32s(bytes: &mut [u8]) {
+  for chunk in bytes.chunks_exact_mut(4) {
+    chunk.reverse();
+  }
 }
 
 impl crate::traits::CalculatesSize for Section {
@@ -1,7 +1,13 @@
 use crate::traits::CalculatesSize;
 use super::Section;
 
-#[derive(Debug, Clone)]
+use byteordered::{Endian, Endianness};
+
+#[cfg(feature = "serde_support")]
+use serde_derive::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
 pub struct Ato1 {
   pub(crate) section: Section,
   pub(crate) _unknown: Vec<u8>, // large collection of 0xFF
@@ -16,13 +22,44 @@ impl Ato1 {
     }
   }
 
+  /// Create an ATO1 section from a list of offsets, where -1 marks an unused entry.
+  pub fn from_offsets(offsets: &[i32], endianness: Endianness) -> Self {
+    let mut bytes = Vec::with_capacity(offsets.len() * 4);
+    for &offset in offsets {
+      endianness.write_i32(&mut bytes, offset).expect("writing to a Vec cannot fail");
+    }
+    Ato1::new_unlinked(bytes)
+  }
+
   pub fn section(&self) -> &Section {
     &self.section
   }
 
+  /// Set the 8 bytes that follow the section size in the section header.
+  pub fn set_section_padding(&mut self, padding: [u8; 8]) {
+    self.section.padding = padding;
+  }
+
   pub fn unknown_bytes(&self) -> &[u8] {
     &self._unknown
   }
+
+  /// Interpret the section data as a list of offsets. Any trailing bytes that do not form a whole
+  /// offset are ignored.
+  pub fn offsets(&self, endianness: Endianness) -> Vec<i32> {
+    self.iter_offsets(endianness).collect()
+  }
+
+  /// Like `offsets`, but reads each offset as it is needed instead of collecting them.
+  pub fn iter_offsets(&self, endianness: Endianness) -> impl Iterator<Item = i32> + '_ {
+    self._unknown.chunks_exact(4)
+      .map(move |mut chunk| endianness.read_i32(&mut chunk).expect("chunk is four bytes"))
+  }
+
+  /// Reverse the byte order of every offset.
+  pub(crate) fn swap_byte_order(&mut self) {
+    super::swap_u32s(&mut self._unknown);
+  }
 }
 
 impl CalculatesSize for Ato1 {
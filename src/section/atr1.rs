@@ -1,7 +1,16 @@
-use crate::traits::CalculatesSize;
+use crate::{
+  error::{Error, Result},
+  traits::CalculatesSize,
+};
 use super::Section;
 
-#[derive(Debug, Clone)]
+#[cfg(feature = "serde_support")]
+use serde_derive::{Deserialize, Serialize};
+
+use byteordered::{Endian, Endianness};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
 pub struct Atr1 {
   pub(crate) section: Section,
   pub(crate) _unknown: Vec<u8>,
@@ -20,9 +29,82 @@ impl Atr1 {
     &self.section
   }
 
+  /// Set the 8 bytes that follow the section size in the section header.
+  pub fn set_section_padding(&mut self, padding: [u8; 8]) {
+    self.section.padding = padding;
+  }
+
   pub fn unknown_bytes(&self) -> &[u8] {
     &self._unknown
   }
+
+  /// The number of attribute entries, read from the start of the section data.
+  pub fn entry_count(&self, endianness: Endianness) -> u32 {
+    self.read_u32_at(0, endianness).unwrap_or(0)
+  }
+
+  /// The size in bytes of each attribute entry.
+  pub fn attribute_size(&self, endianness: Endianness) -> u32 {
+    self.read_u32_at(4, endianness).unwrap_or(0)
+  }
+
+  /// Iterate over the raw bytes of each attribute entry.
+  ///
+  /// Entries that would extend past the end of the section data are not yielded.
+  pub fn entries(&self, endianness: Endianness) -> impl Iterator<Item = &[u8]> {
+    let count = self.entry_count(endianness) as usize;
+    let size = self.attribute_size(endianness) as usize;
+    let data = self._unknown.get(8..).unwrap_or(&[]);
+    let count = if size == 0 { 0 } else { count };
+
+    data.chunks(size.max(1))
+      .filter(move |entry| entry.len() == size)
+      .take(count)
+  }
+
+  /// Grow or shrink the entry list to `count` entries, filling new entries with `fill`.
+  ///
+  /// Existing entries are kept, as is any data after the entries. If the section has no entry header
+  /// yet, one is created with an attribute size of `fill.len()`. Fails if `fill` is not one attribute
+  /// long.
+  pub fn set_entry_count(&mut self, count: u32, fill: &[u8], endianness: Endianness) -> Result<()> {
+    if self._unknown.len() < 8 {
+      self._unknown = vec![0; 8];
+      let mut header = &mut self._unknown[4..];
+      endianness.write_u32(&mut header, fill.len() as u32).map_err(Error::Io)?;
+    }
+
+    let size = self.attribute_size(endianness) as usize;
+    if fill.len() != size {
+      return Err(Error::InvalidAttributeSize { expected: size as u32, actual: fill.len() as u32 });
+    }
+
+    let old_end = (8 + self.entry_count(endianness) as usize * size).min(self._unknown.len());
+    let rest = self._unknown.split_off(old_end);
+    self._unknown.truncate(8 + count as usize * size);
+    while self._unknown.len() < 8 + count as usize * size {
+      self._unknown.extend_from_slice(fill);
+    }
+    self._unknown.extend(rest);
+
+    let mut header = &mut self._unknown[..4];
+    endianness.write_u32(&mut header, count).map_err(Error::Io)?;
+    self.section.size = self._unknown.len() as u32;
+
+    Ok(())
+  }
+
+  /// Reverse the byte order of the entry count and attribute size. The entries themselves are opaque, so
+  /// they are left as they are.
+  pub(crate) fn swap_byte_order(&mut self) {
+    let len = self._unknown.len().min(8);
+    super::swap_u32s(&mut self._unknown[..len]);
+  }
+
+  fn read_u32_at(&self, offset: usize, endianness: Endianness) -> Option<u32> {
+    let mut bytes = self._unknown.get(offset..offset + 4)?;
+    endianness.read_u32(&mut bytes).ok()
+  }
 }
 
 impl CalculatesSize for Atr1 {
@@ -1,20 +1,67 @@
 use crate::{
+  error::{Error, Result},
   traits::{CalculatesSize, Updates},
 };
 use super::Section;
 
+#[cfg(feature = "serde_support")]
+use serde_derive::{Deserialize, Serialize};
+
+use std::collections::BTreeMap;
+
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
 pub struct Lbl1 {
   pub(crate) section: Section,
   pub(crate) groups: Vec<Group>,
   pub(crate) labels: Vec<Label>,
+  pub(crate) read_order: Vec<u32>,
+  pub(crate) preserve_order: bool,
 }
 
 impl Lbl1 {
+  /// Create a label section with `group_count` hash groups, naming the TXT2 value at `indices[i]`
+  /// `names[i]`.
+  ///
+  /// Nintendo's files commonly use 101 groups. Indices without a name get an empty placeholder label, which
+  /// is written as a label with an empty name.
+  /// Fails with `InvalidGroupCount` if `group_count` is 0.
+  pub fn with_group_count(names: &[&str], indices: &[u32], group_count: u32) -> Result<Self> {
+    if names.len() != indices.len() {
+      return Err(Error::LabelIndexCountMismatch { names: names.len(), indices: indices.len() });
+    }
+
+    let label_count = indices.iter().max().map_or(0, |&max| max as usize + 1);
+    let mut labels = vec![Label::new(String::new()); label_count];
+    let mut seen = vec![false; label_count];
+    for (&name, &index) in names.iter().zip(indices) {
+      if std::mem::replace(&mut seen[index as usize], true) {
+        return Err(Error::DuplicateLabelIndex(index));
+      }
+      Label::check_name(name)?;
+      labels[index as usize].name = name.to_string();
+    }
+
+    let mut lbl1 = Lbl1 {
+      section: Section::new(*b"LBL1", 0),
+      groups: Vec::new(),
+      labels,
+      read_order: Vec::new(),
+      preserve_order: false,
+    };
+    lbl1.rebuild_groups(group_count)?;
+    Ok(lbl1)
+  }
+
   pub fn section(&self) -> &Section {
     &self.section
   }
 
+  /// Whether this is an empty section with a size of 0, which is written without a group count.
+  pub(crate) fn is_bare(&self) -> bool {
+    self.section.size == 0 && self.groups.is_empty() && self.labels.is_empty()
+  }
+
   pub fn groups(&self) -> &[Group] {
     &self.groups
   }
@@ -27,24 +74,188 @@ impl Lbl1 {
     &mut self.labels
   }
 
-  fn update_group_offsets(&mut self) {
-    let mut total = 0;
-    let group_len = self.groups.len() as u32;
-    let checksums: Vec<u32> = self.labels.iter()
-      .map(|lbl| lbl.checksum(self)).collect();
-    for (i, group) in self.groups.iter_mut().enumerate() {
-      group.offset = group_len * group.calc_size() as u32
-        + std::mem::size_of::<u32>() as u32 // group count
-        + total;
-      total += self.labels.iter().enumerate()
-        .filter(|(k,_)| checksums[*k] == i as u32)
-        .map(|(_,lbl)| lbl.calc_size() as u32)
-        .sum::<u32>();
+  /// The standard MSBT label hash, giving the index of the group a label called `name` belongs to.
+  ///
+  /// Returns 0 if `group_count` is 0.
+  pub fn hash(name: &str, group_count: u32) -> u32 {
+    hash_bytes(name.as_bytes(), group_count)
+  }
+
+  /// Whether labels are written in the order they were read within each group, rather than by index.
+  pub fn preserve_order(&self) -> bool {
+    self.preserve_order
+  }
+
+  pub fn set_preserve_order(&mut self, preserve_order: bool) {
+    self.preserve_order = preserve_order;
+  }
+
+  /// The indices of labels in the order they appear when written.
+  pub(crate) fn write_order(&self) -> Vec<usize> {
+    let mut order: Vec<usize> = if self.preserve_order {
+      let mut seen = vec![false; self.labels.len()];
+      let mut order: Vec<usize> = self.read_order.iter()
+        .map(|&i| i as usize)
+        .filter(|&i| i < seen.len() && !std::mem::replace(&mut seen[i], true))
+        .collect();
+      // labels added since reading go after the ones that were read
+      order.extend((0..seen.len()).filter(|&i| !seen[i]));
+      order
+    } else {
+      (0..self.labels.len()).collect()
+    };
+
+    order.sort_by_cached_key(|&i| self.labels[i].checksum(self));
+    order
+  }
+
+  /// Put `label` at `index`, adding unnamed labels before it if there are too few.
+  ///
+  /// A label's index is its position, so a label already at `index` is replaced rather than shifted along
+  /// with every label after it. Fails with `DuplicateLabelIndex` if that label has a name.
+  pub(crate) fn set_label_at(&mut self, index: usize, label: Label) -> Result<()> {
+    match self.labels.get_mut(index) {
+      Some(existing) if !existing.name_bytes().is_empty() => Err(Error::DuplicateLabelIndex(index as u32)),
+      Some(existing) => {
+        *existing = label;
+        Ok(())
+      },
+      None => {
+        self.labels.resize(index, Label::new(String::new()));
+        self.labels.push(label);
+        Ok(())
+      },
+    }
+  }
+
+  pub(crate) fn remove_from_read_order(&mut self, index: u32) {
+    self.read_order.retain(|&i| i != index);
+    for i in &mut self.read_order {
+      if *i > index {
+        *i -= 1;
+      }
+    }
+  }
+
+  /// The index of the group the label at `label_index` is stored in.
+  ///
+  /// For labels read from a file this is the group they were listed under, which a well-formed file
+  /// agrees with `Label::checksum` on. Otherwise it is the group the label hashes to.
+  pub fn group_of(&self, label_index: usize) -> Option<usize> {
+    if label_index >= self.labels.len() || self.groups.is_empty() {
+      return None;
+    }
+
+    let listed: usize = self.groups.iter().map(|group| group.label_count as usize).sum();
+    if listed == self.read_order.len() {
+      if let Some(pos) = self.read_order.iter().position(|&i| i as usize == label_index) {
+        let mut end = 0;
+        for (i, group) in self.groups.iter().enumerate() {
+          end += group.label_count as usize;
+          if pos < end {
+            return Some(i);
+          }
+        }
+      }
+    }
+
+    Some(self.labels[label_index].checksum(self) as usize)
+  }
+
+  /// Find the TXT2 index of the label with the given name.
+  pub fn index_of(&self, name: &str) -> Option<usize> {
+    if name.is_empty() {
+      return None;
+    }
+
+    self.labels.iter().position(|lbl| lbl.name == name)
+  }
+
+  /// Map each label name to its TXT2 index. Unnamed labels are left out, and a name used more than once
+  /// maps to its first index, as with `index_of`.
+  pub fn name_to_index(&self) -> BTreeMap<String, usize> {
+    let mut map = BTreeMap::new();
+    for (i, label) in self.labels.iter().enumerate().filter(|(_, label)| !label.name.is_empty()) {
+      map.entry(label.name.clone()).or_insert(i);
     }
+    map
+  }
+
+  /// Iterate over the names of all labels, in TXT2 index order.
+  pub fn label_names(&self) -> impl Iterator<Item = &str> {
+    self.labels.iter()
+      .map(|lbl| lbl.name.as_str())
+      .filter(|name| !name.is_empty())
+  }
+
+  /// Replace the groups with `group_count` new groups and reassign every label to its group.
+  ///
+  /// Fails with `InvalidGroupCount` if `group_count` is 0.
+  pub fn rebuild_groups(&mut self, group_count: u32) -> Result<()> {
+    if group_count == 0 {
+      return Err(Error::InvalidGroupCount(group_count));
+    }
+    self.groups = (0..group_count)
+      .map(|_| Group {
+        label_count: 0,
+        offset: 0,
+      })
+      .collect();
+    self.update();
+    Ok(())
+  }
+
+  /// Recompute the label count and offset of every group from the current labels.
+  pub fn fix_offsets(&mut self) {
+    self.groups = self.computed_groups();
+  }
+
+  /// The groups as `write_lbl1` lays them out for the current labels.
+  pub(crate) fn computed_groups(&self) -> Vec<Group> {
+    let mut groups: Vec<Group> = self.groups.iter()
+      .map(|_| Group {
+        label_count: 0,
+        offset: 0,
+      })
+      .collect();
+    if groups.is_empty() {
+      return groups;
+    }
+
+    let mut sizes = vec![0; groups.len()];
+    for label in &self.labels {
+      let checksum = label.checksum(self) as usize;
+      if let Some(group) = groups.get_mut(checksum) {
+        group.label_count += 1;
+        sizes[checksum] += label.calc_size() as u32;
+      }
+    }
+
+    let mut total = groups.iter().map(|group| group.calc_size() as u32).sum::<u32>()
+      + std::mem::size_of::<u32>() as u32; // group count
+    for (group, size) in groups.iter_mut().zip(sizes) {
+      group.offset = total;
+      total += size;
+    }
+
+    groups
   }
 }
 
-#[derive(Debug, Clone)]
+// the order labels were read in only affects how they are written, so it is not compared
+impl PartialEq for Lbl1 {
+  fn eq(&self, other: &Self) -> bool {
+    self.section == other.section
+      && self.groups == other.groups
+      && self.labels == other.labels
+      && self.preserve_order == other.preserve_order
+  }
+}
+
+impl Eq for Lbl1 {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
 pub struct Group {
   pub(crate) label_count: u32,
   pub(crate) offset: u32,
@@ -60,40 +271,81 @@ impl Group {
   }
 }
 
-#[derive(Debug, Clone)]
+fn hash_bytes(name: &[u8], group_count: u32) -> u32 {
+  let hash: u32 = name.iter()
+    .fold(0, |hash, b| hash.overflowing_mul(Label::HASH_MAGIC).0.overflowing_add(u32::from(*b)).0);
+  hash.checked_rem(group_count).unwrap_or(0)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
 pub struct Label {
   pub(crate) name: String,
+  /// The bytes read from the file, kept when they were not valid UTF-8 and `name` is a lossy copy.
+  pub(crate) raw_name: Option<Vec<u8>>,
 }
 
 impl Label {
   pub(crate) const HASH_MAGIC: u32 = 0x492;
+  /// The longest name that fits, as names are written after a one-byte length.
+  pub(crate) const MAX_NAME_LEN: usize = u8::MAX as usize;
+
+  /// Fail with `LabelTooLong` if `name` does not fit in a label.
+  pub(crate) fn check_name<N: AsRef<[u8]>>(name: N) -> Result<()> {
+    let name = name.as_ref();
+    if name.len() > Label::MAX_NAME_LEN {
+      return Err(Error::LabelTooLong(String::from_utf8_lossy(name).into_owned()));
+    }
+    Ok(())
+  }
+
+  pub(crate) fn new(name: String) -> Self {
+    Label {
+      name,
+      raw_name: None,
+    }
+  }
 
   pub fn name(&self) -> &str {
     &self.name
   }
 
+  /// The bytes this label's name is written as.
+  ///
+  /// These differ from `name` only when the file held invalid UTF-8 that was read lossily.
+  pub fn name_bytes(&self) -> &[u8] {
+    self.raw_name.as_deref().unwrap_or(self.name.as_bytes())
+  }
+
   pub fn set_name<S>(&mut self, name: S)
     where S: Into<String>,
   {
     self.name = name.into();
+    self.raw_name = None;
   }
 
+  /// The index of the group this label belongs to in `lbl1`.
   pub fn checksum(&self, lbl1: &Lbl1) -> u32 {
-    let hash: u32 = self.name.as_bytes().iter()
-      .fold(0, |hash, b| hash.overflowing_mul(Label::HASH_MAGIC).0.overflowing_add(u32::from(*b)).0);
-    hash % lbl1.groups.len() as u32
+    hash_bytes(self.name_bytes(), lbl1.groups.len() as u32)
   }
 }
 
 impl Updates for Lbl1 {
   fn update(&mut self) {
+    if self.is_bare() {
+      return;
+    }
     self.section.size = self.calc_size() as u32 - self.section.calc_size() as u32;
-    self.update_group_offsets();
+    self.fix_offsets();
   }
 }
 
 impl CalculatesSize for Lbl1 {
   fn calc_size(&self) -> usize {
+    if self.is_bare() {
+      return self.section.calc_size();
+    }
+
     self.section.calc_size()
       + std::mem::size_of::<u32>() // group count
       + self.groups.iter().map(&CalculatesSize::calc_size).sum::<usize>()
@@ -110,7 +362,7 @@ impl CalculatesSize for Group {
 impl CalculatesSize for Label {
   fn calc_size(&self) -> usize {
     std::mem::size_of::<u8>() // name length
-      + self.name.as_bytes().len()
+      + self.name_bytes().len()
       + std::mem::size_of::<u32>() // index
   }
 }
@@ -1,25 +1,73 @@
-use crate::traits::CalculatesSize;
+use crate::traits::{CalculatesSize, Updates};
 use super::Section;
 
+#[cfg(feature = "serde_support")]
+use serde_derive::{Deserialize, Serialize};
+
 use std::{collections::BTreeMap};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize), serde(from = "Nli1Fields"))]
 pub struct Nli1 {
   pub(crate) section: Section,
   pub(crate) id_count: u32,
   pub(crate) global_ids: BTreeMap<u32, u32>,
+  /// Message id to the lowest TXT2 index with that id, the reverse of `global_ids`.
+  #[cfg_attr(feature = "serde_support", serde(skip))]
+  pub(crate) indices: BTreeMap<u32, u32>,
+}
+
+/// The fields of `Nli1` that are serialised, from which `indices` is rebuilt.
+#[cfg(feature = "serde_support")]
+#[derive(Deserialize)]
+struct Nli1Fields {
+  section: Section,
+  id_count: u32,
+  global_ids: BTreeMap<u32, u32>,
+}
+
+#[cfg(feature = "serde_support")]
+impl From<Nli1Fields> for Nli1 {
+  fn from(fields: Nli1Fields) -> Self {
+    Nli1::with_section(fields.section, fields.id_count, fields.global_ids)
+  }
 }
 
 impl Nli1 {
   pub fn new_unlinked(id_count: u32, global_ids: BTreeMap<u32, u32>) -> Self {
     let size = std::mem::size_of_val(&id_count) + std::mem::size_of::<u32>() * 2 * global_ids.len();
-    Nli1 {
-      section: Section::new(*b"NLI1", size as u32),
+    Nli1::with_section(Section::new(*b"NLI1", size as u32), id_count, global_ids)
+  }
+
+  pub(crate) fn with_section(section: Section, id_count: u32, global_ids: BTreeMap<u32, u32>) -> Self {
+    let mut nli1 = Nli1 {
+      section,
       id_count,
       global_ids,
+      indices: BTreeMap::new(),
+    };
+    nli1.rebuild_indices();
+    nli1
+  }
+
+  /// Replace the map of TXT2 indices to message ids.
+  pub(crate) fn set_global_ids(&mut self, global_ids: BTreeMap<u32, u32>) {
+    self.global_ids = global_ids;
+    self.rebuild_indices();
+  }
+
+  fn rebuild_indices(&mut self) {
+    self.indices.clear();
+    for (&index, &id) in &self.global_ids {
+      self.indices.entry(id).or_insert(index);
     }
   }
 
+  /// Whether this is an empty section with a size of 0, which is written without an id count.
+  pub(crate) fn is_bare(&self) -> bool {
+    self.section.size == 0 && self.global_ids.is_empty()
+  }
+
   pub fn section(&self) -> &Section {
     &self.section
   }
@@ -28,16 +76,74 @@ impl Nli1 {
     self.id_count
   }
 
+  /// The map of TXT2 indices to message ids.
   pub fn global_ids(&self) -> &BTreeMap<u32, u32> {
     &self.global_ids
   }
+
+  /// Give the TXT2 entry at `index` the message id `id`, returning its old id.
+  ///
+  /// The id count written to the file is always the number of entries in `global_ids`.
+  pub fn insert(&mut self, index: u32, id: u32) -> Option<u32> {
+    let old = self.global_ids.insert(index, id);
+    if let Some(old) = old {
+      self.unlink(old, index);
+    }
+    match self.indices.get(&id) {
+      Some(&lowest) if lowest < index => {},
+      _ => { self.indices.insert(id, index); },
+    }
+    old
+  }
+
+  /// Remove the message id of the TXT2 entry at `index`, returning it.
+  pub fn remove(&mut self, index: u32) -> Option<u32> {
+    let old = self.global_ids.remove(&index)?;
+    self.unlink(old, index);
+    Some(old)
+  }
+
+  /// Drop `index` as the reverse lookup of `id`, falling back to another index with the same id.
+  fn unlink(&mut self, id: u32, index: u32) {
+    if self.indices.get(&id) == Some(&index) {
+      self.indices.remove(&id);
+      if let Some((&other, _)) = self.global_ids.iter().find(|(_, &other_id)| other_id == id) {
+        self.indices.insert(id, other);
+      }
+    }
+  }
+
+  /// Find the TXT2 index of the message with the given id. If several have it, this is the lowest.
+  pub fn txt2_index(&self, id: u32) -> Option<u32> {
+    self.indices.get(&id).copied()
+  }
+
+  /// Find the message id of the TXT2 entry at `index`.
+  pub fn id_for_index(&self, index: u32) -> Option<u32> {
+    self.global_ids.get(&index).copied()
+  }
+
+  /// Iterate over `(id, txt2 index)` pairs, ordered by TXT2 index.
+  pub fn entries(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+    self.global_ids.iter().map(|(&index, &id)| (id, index))
+  }
+}
+
+impl Updates for Nli1 {
+  fn update(&mut self) {
+    if self.is_bare() {
+      return;
+    }
+    self.id_count = self.global_ids.len() as u32;
+    self.section.size = (self.calc_size() - self.section.calc_size()) as u32;
+  }
 }
 
 impl CalculatesSize for Nli1 {
   fn calc_size(&self) -> usize {
     let mut base = self.section.calc_size();
 
-    if !self.global_ids.is_empty() {
+    if !self.is_bare() {
       base += std::mem::size_of_val(&self.id_count)
         + std::mem::size_of::<u32>() * self.global_ids.len() * 2;
     }
@@ -1,7 +1,13 @@
 use crate::traits::CalculatesSize;
 use super::Section;
 
-#[derive(Debug, Clone)]
+use byteordered::{Endian, Endianness};
+
+#[cfg(feature = "serde_support")]
+use serde_derive::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
 pub struct Tsy1 {
   pub(crate) section: Section,
   pub(crate) _unknown: Vec<u8>, // tons of unknown data
@@ -16,13 +22,40 @@ impl Tsy1 {
     }
   }
 
+  /// Create a TSY1 section from one style index per TXT2 value.
+  pub fn from_indices(indices: &[u32], endianness: Endianness) -> Self {
+    let mut bytes = Vec::with_capacity(indices.len() * 4);
+    for &index in indices {
+      endianness.write_u32(&mut bytes, index).expect("writing to a Vec cannot fail");
+    }
+    Tsy1::new_unlinked(bytes)
+  }
+
   pub fn section(&self) -> &Section {
     &self.section
   }
 
+  /// Set the 8 bytes that follow the section size in the section header.
+  pub fn set_section_padding(&mut self, padding: [u8; 8]) {
+    self.section.padding = padding;
+  }
+
   pub fn unknown_bytes(&self) -> &[u8] {
     &self._unknown
   }
+
+  /// Interpret the section data as one style index per TXT2 value. Any trailing bytes that do not form
+  /// a whole index are ignored.
+  pub fn style_indices(&self, endianness: Endianness) -> Vec<u32> {
+    self._unknown.chunks_exact(4)
+      .map(|mut chunk| endianness.read_u32(&mut chunk).expect("chunk is four bytes"))
+      .collect()
+  }
+
+  /// Reverse the byte order of every style index.
+  pub(crate) fn swap_byte_order(&mut self) {
+    super::swap_u32s(&mut self._unknown);
+  }
 }
 
 impl CalculatesSize for Tsy1 {
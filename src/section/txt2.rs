@@ -1,43 +1,533 @@
-use crate::traits::{CalculatesSize, Updates};
+use crate::{
+  Encoding,
+  error::{Error, Result},
+  traits::{CalculatesSize, Updates},
+};
 use super::Section;
-use std::io::{Read, Cursor};
+
+use byteordered::Endianness;
+
+use std::{
+  collections::BTreeMap,
+  fmt,
+  hash::{Hash, Hasher},
+  io::{Read, Cursor},
+};
+
+#[cfg(feature = "serde_support")]
+use serde_derive::{Deserialize, Serialize};
+
+pub mod control;
 
 pub const TAG_START: u16 = 0x0E;
 pub const TAG_END: u16 = 0x0F;
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
 pub struct Txt2 {
   pub(crate) section: Section,
   pub(crate) values: Vec<Vec<Token>>,
+  pub(crate) encoding: Encoding,
+  #[cfg_attr(feature = "serde_support", serde(with = "crate::EndiannessDef"))]
+  pub(crate) endianness: Endianness,
+  pub(crate) read_offsets: Vec<u32>,
+}
+
+// the offsets read from a file are only kept for diagnostics, so they are not compared
+impl PartialEq for Txt2 {
+  fn eq(&self, other: &Self) -> bool {
+    self.section == other.section
+      && self.values == other.values
+      && self.encoding == other.encoding
+      && self.endianness == other.endianness
+  }
+}
+
+impl Eq for Txt2 {}
+
+impl Hash for Txt2 {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    self.section.hash(state);
+    self.values.hash(state);
+    self.encoding.hash(state);
+    self.endianness.hash(state);
+  }
 }
 
 impl Txt2 {
+  /// Create a TXT2 section with one null-terminated value for each string.
+  pub fn from_strings<I, S>(strings: I, encoding: Encoding, endianness: Endianness) -> Self
+    where I: IntoIterator<Item = S>,
+          S: AsRef<str>,
+  {
+    let values = strings.into_iter()
+      .map(|string| {
+        let string = string.as_ref();
+        let mut tokens = Vec::with_capacity(2);
+        if !string.is_empty() {
+          tokens.push(Token::from_text(string, encoding, endianness));
+        }
+        tokens.push(Token::Padding(Vec::new()));
+        tokens
+      })
+      .collect();
+
+    let mut txt2 = Txt2 {
+      section: Section::new(*b"TXT2", 0),
+      values,
+      encoding,
+      endianness,
+      read_offsets: Vec::new(),
+    };
+    txt2.update();
+    txt2
+  }
+
   pub fn section(&self) -> &Section {
     &self.section
   }
 
+  /// Whether this is an empty section with a size of 0, which is written without a value count.
+  pub(crate) fn is_bare(&self) -> bool {
+    self.section.size == 0 && self.values.is_empty()
+  }
+
   pub fn values(&self) -> &[Vec<Token>] {
     &self.values
   }
+
+  /// The bytes of each value as they would be written.
+  pub fn raw_entries(&self) -> impl Iterator<Item = Vec<u8>> + '_ {
+    self.values.iter().map(move |value| to_bytes(value, self.encoding, self.endianness))
+  }
+
+  /// The value offsets as they were in the file this section was read from, or nothing if it was not read.
+  pub fn read_offsets(&self) -> &[u32] {
+    &self.read_offsets
+  }
+
+  /// The value offsets as they would be written for the current values.
+  pub fn computed_offsets(&self) -> Vec<u32> {
+    let mut offset = (std::mem::size_of::<u32>() * (self.values.len() + 1)) as u32;
+    self.values.iter()
+      .map(|value| {
+        let start = offset;
+        offset += value.iter().map(|token| token.byte_len(self.encoding)).sum::<usize>() as u32;
+        start
+      })
+      .collect()
+  }
+
+  /// Mutable access to the values.
+  ///
+  /// The section size is only recomputed when editing through `Msbt::txt2_mut`; changes made to a `Txt2`
+  /// held any other way leave `section().size()` stale.
+  pub fn values_mut(&mut self) -> &mut [Vec<Token>] {
+    &mut self.values
+  }
+
+  /// The indices of values that do not end with a null terminator.
+  pub fn unterminated_values(&self) -> impl Iterator<Item = usize> + '_ {
+    self.values.iter()
+      .enumerate()
+      .filter(|(_, value)| !has_terminator(value))
+      .map(|(i, _)| i)
+  }
+
+  /// Append a null terminator to every value that lacks one, returning how many were changed.
+  ///
+  /// Values read from a file are left as they are unless this is called, so that they write back
+  /// unchanged.
+  pub fn terminate_values(&mut self) -> usize {
+    let mut count = 0;
+    for value in self.values.iter_mut().filter(|value| !has_terminator(value)) {
+      value.push(Token::Padding(Vec::new()));
+      count += 1;
+    }
+    self.update();
+    count
+  }
+
+  /// Shorten the value at `index` to at most `max` bytes by dropping text and control codes from the end,
+  /// returning the number of bytes removed.
+  ///
+  /// Text is only cut between characters and control codes are dropped whole, so the result is always
+  /// valid in this section's encoding. If the cut would separate a tag start from its tag end, the tag start
+  /// and everything after it are dropped too. A final null terminator is kept, so this fails if `max` is
+  /// smaller than it, or if the text being cut cannot be decoded.
+  pub fn truncate_to_bytes(&mut self, index: usize, max: usize) -> Result<usize> {
+    let value = self.values.get(index).ok_or(Error::InvalidMessageIndex(index))?;
+    let (body, terminator) = match value.split_last() {
+      Some((last @ Token::Padding(_), body)) => (body, Some(last)),
+      _ => (&value[..], None),
+    };
+    let terminator_len = terminator.map_or(0, |token| token.byte_len(self.encoding));
+    let budget = max.checked_sub(terminator_len)
+      .ok_or(Error::CannotTruncate { max, min: terminator_len })?;
+
+    let mut truncated = Vec::with_capacity(value.len());
+    let mut len = 0;
+    for token in body {
+      let token_len = token.byte_len(self.encoding);
+      if len + token_len <= budget {
+        truncated.push(token.clone());
+        len += token_len;
+        continue;
+      }
+
+      if let Token::Text(bytes) = token {
+        let text = try_decode_text(bytes, self.encoding, self.endianness)?;
+        let unit = self.encoding.code_unit_size();
+        let mut fit = 0;
+        for c in text.chars() {
+          let width = if unit == 2 { c.len_utf16() * 2 } else { c.len_utf8() };
+          if len + fit + width > budget {
+            break;
+          }
+          fit += width;
+        }
+        if fit > 0 {
+          truncated.push(Token::Text(bytes[..fit].to_vec()));
+          len += fit;
+        }
+      }
+      break;
+    }
+    // a tag whose end was cut off would leave its formatting open, so drop it with whatever followed it
+    if let Some(start) = first_unclosed_tag(body, truncated.len()) {
+      truncated.truncate(start);
+      len = truncated.iter().map(|token| token.byte_len(self.encoding)).sum();
+    }
+    truncated.extend(terminator.cloned());
+
+    let removed = self.byte_len(index).expect("index is in range") - (len + terminator_len);
+    self.values[index] = truncated;
+    self.update();
+    Ok(removed)
+  }
+
+  pub fn encoding(&self) -> Encoding {
+    self.encoding
+  }
+
+  pub fn endianness(&self) -> Endianness {
+    self.endianness
+  }
+
+  /// Re-encode the text of every value into `encoding`.
+  ///
+  /// Control codes are kept as they are. Nothing is changed if any text cannot be decoded.
+  pub fn convert_encoding(&mut self, encoding: Encoding) -> Result<()> {
+    if let Encoding::Other(x) = encoding {
+      return Err(Error::InvalidEncoding(x));
+    }
+
+    let mut values = Vec::with_capacity(self.values.len());
+    for value in &self.values {
+      let mut tokens = Vec::with_capacity(value.len());
+      for token in value {
+        tokens.push(match token {
+          Token::Text(bytes) => {
+            let text = try_decode_text(bytes, self.encoding, self.endianness)?;
+            Token::from_text(&text, encoding, self.endianness)
+          },
+          _ => token.clone(),
+        });
+      }
+      values.push(tokens);
+    }
+
+    self.values = values;
+    self.encoding = encoding;
+    self.update();
+
+    Ok(())
+  }
+
+  /// Change the byte order of this section, swapping the bytes of any UTF-16 text to match.
+  ///
+  /// Control code parameters are opaque, so they are swapped as a list of u16s, which is what most control
+  /// codes use.
+  pub fn set_endianness(&mut self, endianness: Endianness) {
+    if endianness == self.endianness {
+      return;
+    }
+
+    for token in self.values.iter_mut().flatten() {
+      match token {
+        Token::Text(bytes) | Token::Padding(bytes) if self.encoding == Encoding::Utf16 => swap_u16s(bytes),
+        Token::TagStart(_, _, params) => swap_u16s(params),
+        _ => {},
+      }
+    }
+    self.endianness = endianness;
+  }
+
+  /// The tokens of the value at `index`.
+  pub fn tokens(&self, index: usize) -> Option<&[Token]> {
+    self.values.get(index).map(Vec::as_slice)
+  }
+
+  /// Group the indices of values by content, so each distinct value appears once.
+  pub fn dedup_map(&self) -> BTreeMap<&[Token], Vec<usize>> {
+    let mut map: BTreeMap<&[Token], Vec<usize>> = BTreeMap::new();
+    for (i, value) in self.values.iter().enumerate() {
+      map.entry(value).or_default().push(i);
+    }
+    map
+  }
+
+  /// The number of characters in the text of the value at `index`, not counting control codes.
+  ///
+  /// Text that cannot be decoded counts one U+FFFD per invalid sequence, as with `get_string`.
+  pub fn char_count(&self, index: usize) -> Option<usize> {
+    self.get_string(index, None).map(|text| text.chars().count())
+  }
+
+  /// The size in bytes of the value at `index` when written, including control codes and terminator.
+  pub fn byte_len(&self, index: usize) -> Option<usize> {
+    self.values.get(index)
+      .map(|value| value.iter().map(|token| token.byte_len(self.encoding)).sum())
+  }
+
+  /// Display the value at `index` as text, with control codes written as `{group.tag:params}`.
+  pub fn display(&self, index: usize) -> Option<DisplayTxt2<'_>> {
+    self.display_with(index, DefaultControlFormatter)
+  }
+
+  /// Display the value at `index` as text, with control codes written by `formatter`.
+  pub fn display_with<F>(&self, index: usize, formatter: F) -> Option<DisplayTxt2<'_, F>>
+    where F: ControlFormatter,
+  {
+    self.values.get(index).map(|value| DisplayTxt2 {
+      txt2: self,
+      value,
+      formatter,
+    })
+  }
+
+  /// Decode the text of the value at `index` into a `String`.
+  ///
+  /// Control codes are replaced by `control_placeholder` if given, otherwise they are skipped. Text that
+  /// cannot be decoded is replaced with U+FFFD; use `try_get_string` to get an error instead.
+  pub fn get_string(&self, index: usize, control_placeholder: Option<&str>) -> Option<String> {
+    let value = self.values.get(index)?;
+    let string = join_text(value, control_placeholder, |bytes| {
+      Ok(decode_text(bytes, self.encoding, self.endianness))
+    });
+    string.ok()
+  }
+
+  /// Decode the text of the value at `index` into a `String`, failing on text that is not valid in this
+  /// section's encoding, such as lone UTF-16 surrogates.
+  pub fn try_get_string(&self, index: usize, control_placeholder: Option<&str>) -> Result<Option<String>> {
+    self.values.get(index)
+      .map(|value| join_text(value, control_placeholder, |bytes| {
+        try_decode_text(bytes, self.encoding, self.endianness)
+      }))
+      .transpose()
+  }
+}
+
+/// Writes control codes for `DisplayTxt2`.
+pub trait ControlFormatter {
+  fn fmt_tag_start(&self, f: &mut fmt::Formatter<'_>, group: u16, tag: u16, params: &[u8]) -> fmt::Result;
+
+  fn fmt_tag_end(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str("{/}")
+  }
+}
+
+/// Writes control codes as `{group.tag}`, or `{group.tag:params}` with the parameters in hex.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultControlFormatter;
+
+impl ControlFormatter for DefaultControlFormatter {
+  fn fmt_tag_start(&self, f: &mut fmt::Formatter<'_>, group: u16, tag: u16, params: &[u8]) -> fmt::Result {
+    if params.is_empty() {
+      write!(f, "{{{}.{}}}", group, tag)
+    } else {
+      write!(f, "{{{}.{}:{}}}", group, tag, hex::encode(params))
+    }
+  }
+}
+
+/// A TXT2 value shown as text, made by `Txt2::display`.
+///
+/// Text that cannot be decoded is shown as U+FFFD.
+pub struct DisplayTxt2<'a, F = DefaultControlFormatter> {
+  txt2: &'a Txt2,
+  value: &'a [Token],
+  formatter: F,
+}
+
+impl<'a, F> fmt::Display for DisplayTxt2<'a, F>
+  where F: ControlFormatter,
+{
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    for token in self.value {
+      match token {
+        Token::Text(bytes) => f.write_str(&decode_text(bytes, self.txt2.encoding, self.txt2.endianness))?,
+        Token::TagStart(group, tag, params) => self.formatter.fmt_tag_start(f, *group, *tag, params)?,
+        Token::TagEnd => self.formatter.fmt_tag_end(f)?,
+        Token::Padding(_) => {},
+      }
+    }
+
+    Ok(())
+  }
+}
+
+/// A TXT2 section whose values are only decoded when asked for.
+///
+/// Obtained from `Msbt::from_reader_lazy` or `MsbtReader::read_txt2_lazy`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LazyTxt2 {
+  pub(crate) section: Section,
+  pub(crate) data: Vec<u8>,
+  pub(crate) offsets: Vec<u32>,
+  pub(crate) encoding: Encoding,
+  pub(crate) endianness: Endianness,
+}
+
+impl LazyTxt2 {
+  pub fn section(&self) -> &Section {
+    &self.section
+  }
+
+  pub fn len(&self) -> usize {
+    self.offsets.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.offsets.is_empty()
+  }
+
+  /// The undecoded bytes of the value at `index`.
+  pub fn get_raw(&self, index: usize) -> Option<&[u8]> {
+    let start = *self.offsets.get(index)? as usize;
+    let end = self.offsets.get(index + 1)
+      .map(|&offset| offset as usize)
+      .unwrap_or_else(|| self.data.len());
+    self.data.get(start..end)
+  }
+
+  /// Decode the value at `index` into tokens.
+  pub fn decode(&self, index: usize) -> Option<Vec<Token>> {
+    self.get_raw(index).map(|raw| parse_bytes(raw, self.encoding, self.endianness))
+  }
+
+  /// Decode every value, producing a full `Txt2`.
+  pub fn into_txt2(self) -> Txt2 {
+    let values = (0..self.len())
+      .filter_map(|i| self.decode(i))
+      .collect();
+
+    Txt2 {
+      section: self.section,
+      values,
+      encoding: self.encoding,
+      endianness: self.endianness,
+      read_offsets: self.offsets,
+    }
+  }
+}
+
+/// The index of the first tag start in `value` whose tag end comes at or after `cut`, if it starts before it.
+fn first_unclosed_tag(value: &[Token], cut: usize) -> Option<usize> {
+  let mut open = Vec::new();
+  let mut first = None;
+  for (i, token) in value.iter().enumerate() {
+    match token {
+      Token::TagStart(..) => open.push(i),
+      Token::TagEnd => if let Some(start) = open.pop() {
+        if start < cut && i >= cut {
+          first = Some(first.map_or(start, |first: usize| first.min(start)));
+        }
+      },
+      _ => {},
+    }
+  }
+  first
+}
+
+pub(crate) fn has_terminator(value: &[Token]) -> bool {
+  matches!(value.last(), Some(Token::Padding(_)))
+}
+
+fn join_text<F>(value: &[Token], control_placeholder: Option<&str>, decode: F) -> Result<String>
+  where F: Fn(&[u8]) -> Result<String>,
+{
+  let mut string = String::new();
+  for token in value {
+    match token {
+      Token::Text(bytes) => string.push_str(&decode(bytes)?),
+      Token::TagStart(..) | Token::TagEnd => if let Some(placeholder) = control_placeholder {
+        string.push_str(placeholder);
+      },
+      Token::Padding(_) => {},
+    }
+  }
+
+  Ok(string)
+}
+
+fn utf16_units(bytes: &[u8], endianness: Endianness) -> Vec<u16> {
+  bytes.chunks_exact(2)
+    .map(|unit| match endianness {
+      Endianness::Big => u16::from_be_bytes([unit[0], unit[1]]),
+      Endianness::Little => u16::from_le_bytes([unit[0], unit[1]]),
+    })
+    .collect()
+}
+
+fn decode_text(bytes: &[u8], encoding: Encoding, endianness: Endianness) -> String {
+  match encoding {
+    Encoding::Utf8 | Encoding::Other(_) => String::from_utf8_lossy(bytes).into_owned(),
+    Encoding::Utf16 => {
+      let mut text = String::from_utf16_lossy(&utf16_units(bytes, endianness));
+      if bytes.len() % 2 == 1 {
+        text.push(std::char::REPLACEMENT_CHARACTER);
+      }
+      text
+    },
+  }
+}
+
+pub(crate) fn try_decode_text(bytes: &[u8], encoding: Encoding, endianness: Endianness) -> Result<String> {
+  match encoding {
+    Encoding::Utf8 => std::str::from_utf8(bytes).map(str::to_owned).map_err(Error::InvalidBorrowedUtf8),
+    Encoding::Utf16 if bytes.len() % 2 == 1 => Err(Error::OddUtf16Length(bytes.len())),
+    Encoding::Utf16 => String::from_utf16(&utf16_units(bytes, endianness)).map_err(Error::InvalidUtf16),
+    Encoding::Other(x) => Err(Error::InvalidEncoding(x)),
+  }
 }
 
 impl CalculatesSize for Txt2 {
   fn calc_size(&self) -> usize {
+    if self.is_bare() {
+      return self.section.calc_size();
+    }
+
     self.section.calc_size()
       + std::mem::size_of::<u32>() // value count
       + std::mem::size_of::<u32>() * self.values.len() // offsets
       + self.values.iter()
-        .map(|v| v.iter().flat_map(|vv| vv.to_bytes())
-        .collect::<Vec<u8>>().len()).sum::<usize>()
+        .map(|v| v.iter().map(|vv| vv.byte_len(self.encoding)).sum::<usize>())
+        .sum::<usize>()
   }
 }
 
 impl Updates for Txt2 {
   fn update(&mut self) {
+    if self.is_bare() {
+      return;
+    }
+
     let value_count = self.values.len() as u32;
     let values_size = self.values.iter()
-      .map(|v| v.iter().flat_map(|vv| vv.to_bytes())
-      .collect::<Vec<u8>>().len()).sum::<usize>();
+      .map(|v| v.iter().map(|vv| vv.byte_len(self.encoding)).sum::<usize>())
+      .sum::<usize>();
     let new_size = values_size
       + value_count as usize * std::mem::size_of::<u32>() // all offsets
       + std::mem::size_of_val(&value_count);
@@ -45,71 +535,185 @@ impl Updates for Txt2 {
   }
 }
 
-pub fn parse_bytes(bytes: &[u8]) -> Vec<Token> {
-  let mut rdr = byteordered::ByteOrdered::le(Cursor::new(bytes));
+fn u16_bytes(value: u16, endianness: Endianness) -> [u8; 2] {
+  match endianness {
+    Endianness::Big => value.to_be_bytes(),
+    Endianness::Little => value.to_le_bytes(),
+  }
+}
+
+/// Write a single code unit of the given encoding.
+fn unit_bytes(value: u16, encoding: Encoding, endianness: Endianness) -> Vec<u8> {
+  match encoding.code_unit_size() {
+    2 => u16_bytes(value, endianness).to_vec(),
+    _ => vec![value as u8],
+  }
+}
+
+/// Parse the raw bytes of a TXT2 value into tokens.
+///
+/// Control codes are read in code units of `encoding`, with their fields in `endianness`. Trailing bytes
+/// too short to form a whole code unit are kept at the end of a text token, so they are written back
+/// unchanged; decoding that text gives U+FFFD for them, or an error from `try_get_string`.
+pub fn parse_bytes(bytes: &[u8], encoding: Encoding, endianness: Endianness) -> Vec<Token> {
+  let mut rdr = byteordered::ByteOrdered::runtime(Cursor::new(bytes), endianness);
   let mut tokens: Vec<Token> = Vec::new();
-  
-  while let Ok(byte) = rdr.read_u16() {
-    match byte {
+  let mut unit = vec![0; encoding.code_unit_size()];
+
+  loop {
+    let rest = bytes.get(rdr.inner_mut().position() as usize..).unwrap_or_default();
+    if rest.is_empty() {
+      break;
+    }
+    if rest.len() < unit.len() {
+      push_text(&mut tokens, rest);
+      break;
+    }
+    if rdr.read_exact(&mut unit).is_err() {
+      break;
+    }
+    let code = match unit[..] {
+      [a, b] => match endianness {
+        Endianness::Big => u16::from_be_bytes([a, b]),
+        Endianness::Little => u16::from_le_bytes([a, b]),
+      },
+      _ => u16::from(unit[0]),
+    };
+    match code {
       TAG_START => {
-        let group_code = rdr.read_u16().unwrap();
-        let tag_code = rdr.read_u16().unwrap();
-        let params_size = rdr.read_u16().unwrap() as usize;
-        let mut params = vec![0; params_size];
-        rdr.read_exact(&mut params).unwrap();
-        tokens.push(Token::TagStart(group_code, tag_code, params));
+        let start = rdr.inner_mut().position() as usize;
+        match parse_tag(&bytes[start..], endianness) {
+          Some((token, len)) => {
+            tokens.push(token);
+            rdr.inner_mut().set_position((start + len) as u64);
+          },
+          // a tag cut off by the end of the value is kept as text, so it is written back unchanged
+          None => {
+            push_text(&mut tokens, rest);
+            break;
+          },
+        }
       },
       TAG_END => tokens.push(Token::TagEnd),
       0x00 => {
         // Some games e.g. mario & luigi have null bytes in text
-        let mut padding_end = Vec::new();
-        rdr.read_to_end(&mut padding_end).unwrap();
-        tokens.push(Token::Padding(padding_end));
+        tokens.push(Token::Padding(rest[unit.len()..].to_vec()));
+        break;
       },
-      _ => {
-        if tokens.len() == 0 {
-          tokens.push(Token::Text(Vec::new()));
-        } else {
-          match tokens.get(tokens.len() - 1) {
-            Some(Token::Text(_)) => (),
-            _ => tokens.push(Token::Text(Vec::new()))
-          };
-        }
-        let last_i = tokens.len() - 1;
-        if let Some(Token::Text(ref mut b)) = tokens.get_mut(last_i) {
-          b.extend(&byte.to_le_bytes());
-        }
-      }
+      _ => push_text(&mut tokens, &unit),
     };
   };
 
   tokens
 }
 
-#[derive(Debug, Clone)]
+/// Read the group, tag and parameters of a control code from `bytes`, which start just after its
+/// opening code unit, returning the token and the number of bytes it used.
+fn parse_tag(bytes: &[u8], endianness: Endianness) -> Option<(Token, usize)> {
+  let field = |i: usize| {
+    let b = bytes.get(i * 2..i * 2 + 2)?;
+    Some(match endianness {
+      Endianness::Big => u16::from_be_bytes([b[0], b[1]]),
+      Endianness::Little => u16::from_le_bytes([b[0], b[1]]),
+    })
+  };
+  let (group, tag, params_size) = (field(0)?, field(1)?, field(2)? as usize);
+  let params = bytes.get(6..6 + params_size)?;
+  Some((Token::TagStart(group, tag, params.to_vec()), 6 + params_size))
+}
+
+/// Reverse the byte order of every whole u16 in `bytes`, leaving a trailing odd byte as it is.
+fn swap_u16s(bytes: &mut [u8]) {
+  for unit in bytes.chunks_exact_mut(2) {
+    unit.swap(0, 1);
+  }
+}
+
+/// Add `bytes` to the text token at the end of `tokens`, starting a new one if needed.
+fn push_text(tokens: &mut Vec<Token>, bytes: &[u8]) {
+  match tokens.last_mut() {
+    Some(Token::Text(text)) => text.extend_from_slice(bytes),
+    _ => tokens.push(Token::Text(bytes.to_vec())),
+  }
+}
+
+/// Serialise tokens back into the raw bytes of a TXT2 value. This is the reverse of `parse_bytes`.
+pub fn to_bytes(tokens: &[Token], encoding: Encoding, endianness: Endianness) -> Vec<u8> {
+  tokens.iter()
+    .flat_map(|token| token.to_bytes(encoding, endianness))
+    .collect()
+}
+
+/// Decode the text of a raw TXT2 value, such as one dumped without the rest of its file.
+///
+/// Control codes are skipped, as is a leading byte order mark. Fails on text that is not valid in
+/// `encoding`.
+pub fn decode(bytes: &[u8], encoding: Encoding, endianness: Endianness) -> Result<String> {
+  let tokens = parse_bytes(bytes, encoding, endianness);
+  let text = join_text(&tokens, None, |bytes| try_decode_text(bytes, encoding, endianness))?;
+  match text.strip_prefix('\u{FEFF}') {
+    Some(rest) => Ok(rest.to_string()),
+    None => Ok(text),
+  }
+}
+
+/// Encode `text` as a raw TXT2 value with a null terminator, the reverse of `decode`.
+pub fn encode(text: &str, encoding: Encoding, endianness: Endianness) -> Vec<u8> {
+  let tokens = [Token::from_text(text, encoding, endianness), Token::Padding(Vec::new())];
+  to_bytes(&tokens, encoding, endianness)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
 pub enum Token {
+  /// A control code: group, tag and parameter bytes.
   TagStart(u16, u16, Vec<u8>),
+  /// Raw text bytes, in the file's encoding and byte order.
   Text(Vec<u8>),
   TagEnd,
+  /// A null terminator, followed by any bytes after it.
   Padding(Vec<u8>)
 }
 
 impl Token {
-  pub fn to_bytes(&self) -> Vec<u8> {
+  /// Create a text token from a string.
+  pub fn from_text(text: &str, encoding: Encoding, endianness: Endianness) -> Self {
+    let bytes = match encoding {
+      Encoding::Utf16 => text.encode_utf16()
+        .flat_map(|unit| u16_bytes(unit, endianness).to_vec())
+        .collect(),
+      Encoding::Utf8 | Encoding::Other(_) => text.as_bytes().to_vec(),
+    };
+    Token::Text(bytes)
+  }
+
+  /// The size of this token when written with the given encoding.
+  pub fn byte_len(&self, encoding: Encoding) -> usize {
+    let unit = encoding.code_unit_size();
+    match self {
+      Self::TagStart(_, _, param_bytes) => unit
+        + std::mem::size_of::<u16>() * 3 // group, tag, params size
+        + param_bytes.len(),
+      Self::Text(bytes) => bytes.len(),
+      Self::TagEnd => unit,
+      Self::Padding(padding) => unit + padding.len(),
+    }
+  }
+
+  pub fn to_bytes(&self, encoding: Encoding, endianness: Endianness) -> Vec<u8> {
     match self {
       Self::TagStart(group_code, tag_code, param_bytes) => {
-        let mut result = Vec::new();
-        result.extend(&TAG_START.to_le_bytes());
-        result.extend(&group_code.to_le_bytes());
-        result.extend(&tag_code.to_le_bytes());
+        let mut result = unit_bytes(TAG_START, encoding, endianness);
+        result.extend(&u16_bytes(*group_code, endianness));
+        result.extend(&u16_bytes(*tag_code, endianness));
+        result.extend(&u16_bytes(param_bytes.len() as u16, endianness));
         result.extend(param_bytes);
         result
       },
       Self::Text(bytes) => bytes.to_vec(),
-      Self::TagEnd => TAG_END.to_le_bytes().to_vec(),
+      Self::TagEnd => unit_bytes(TAG_END, encoding, endianness),
       Self::Padding(padding) => {
-        let mut result = Vec::new();
-        result.extend(&0u16.to_le_bytes());
+        let mut result = encoding.null_terminator().to_vec();
         result.extend(padding);
         result
       }
@@ -0,0 +1,52 @@
+//! Control codes shared by most games, in the system group.
+//!
+//! Games add their own groups on top of these, so tokens outside this set are not necessarily invalid.
+
+use super::Token;
+
+use byteordered::Endianness;
+
+/// The group holding the control codes built into the message system.
+pub const GROUP_SYSTEM: u16 = 0;
+
+/// Ruby (furigana) text. The parameters are the byte length of the ruby text followed by the text.
+pub const TAG_RUBY: u16 = 0;
+/// Change the font. The parameter is the font index.
+pub const TAG_FONT: u16 = 1;
+/// Change the font size. The parameter is the size as a percentage.
+pub const TAG_SIZE: u16 = 2;
+/// Change the text colour. The parameter is an index into the game's colour table.
+pub const TAG_COLOR: u16 = 3;
+/// Start a new page.
+pub const TAG_PAGE_BREAK: u16 = 4;
+
+impl Token {
+  /// Whether this is a system control code with the given tag.
+  pub fn is_system_tag(&self, tag: u16) -> bool {
+    matches!(self, Token::TagStart(GROUP_SYSTEM, t, _) if *t == tag)
+  }
+
+  /// Change the text colour to `index` in the game's colour table.
+  pub fn color(index: u16, endianness: Endianness) -> Self {
+    system_tag(TAG_COLOR, index, endianness)
+  }
+
+  /// Change the font to `index`.
+  pub fn font(index: u16, endianness: Endianness) -> Self {
+    system_tag(TAG_FONT, index, endianness)
+  }
+
+  /// Change the font size to `percent` of the normal size.
+  pub fn font_size(percent: u16, endianness: Endianness) -> Self {
+    system_tag(TAG_SIZE, percent, endianness)
+  }
+
+  /// Start a new page.
+  pub fn page_break() -> Self {
+    Token::TagStart(GROUP_SYSTEM, TAG_PAGE_BREAK, Vec::new())
+  }
+}
+
+fn system_tag(tag: u16, param: u16, endianness: Endianness) -> Token {
+  Token::TagStart(GROUP_SYSTEM, tag, super::u16_bytes(param, endianness).to_vec())
+}
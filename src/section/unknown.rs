@@ -0,0 +1,41 @@
+use crate::traits::CalculatesSize;
+use super::Section;
+
+#[cfg(feature = "serde_support")]
+use serde_derive::{Deserialize, Serialize};
+
+/// A section with a magic this crate does not recognise, kept as raw bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub struct UnknownSection {
+  pub(crate) section: Section,
+  pub(crate) data: Vec<u8>,
+}
+
+impl UnknownSection {
+  pub fn new_unlinked<V: Into<Vec<u8>>>(magic: [u8; 4], data: V) -> Self {
+    let data = data.into();
+    UnknownSection {
+      section: Section::new(magic, data.len() as u32),
+      data,
+    }
+  }
+
+  pub fn section(&self) -> &Section {
+    &self.section
+  }
+
+  pub fn magic(&self) -> [u8; 4] {
+    self.section.magic
+  }
+
+  pub fn data(&self) -> &[u8] {
+    &self.data
+  }
+}
+
+impl CalculatesSize for UnknownSection {
+  fn calc_size(&self) -> usize {
+    self.section.calc_size() + self.data.len()
+  }
+}
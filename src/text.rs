@@ -0,0 +1,202 @@
+//! A plain `label = value` text format for editing messages by hand.
+//!
+//! Each line holds one message. In labels and values, `\\`, `\n` and `\r` stand for a backslash, a
+//! newline and a carriage return, and labels escape `=` as `\=`. Values mark control codes as
+//! `\{group,tag,params}` with the parameters in hex, and tag ends as `\}`. The usual trailing null
+//! terminator is left out; any other one is written as `\0[bytes]`, and a value with no terminator
+//! ends in `\-`.
+
+use crate::{
+  Msbt,
+  SectionTag,
+  error::{Error, Result},
+  section::txt2::{self, Token},
+};
+
+use std::collections::BTreeMap;
+
+impl Msbt {
+  /// Write every labelled message as a `label = value` line.
+  pub fn dump_text(&self) -> Result<String> {
+    self.lbl1.as_ref().ok_or(Error::MissingSection(SectionTag::Lbl1))?;
+    let txt2 = self.txt2.as_ref().ok_or(Error::MissingSection(SectionTag::Txt2))?;
+
+    let mut text = String::new();
+    for (label, value) in self.entries() {
+      escape_label(label, &mut text);
+      text.push_str(" = ");
+      escape_value(value, txt2, &mut text)?;
+      text.push('\n');
+    }
+
+    Ok(text)
+  }
+
+  /// Copy `base`, replacing the values of the messages listed in `text`, as written by `dump_text`.
+  ///
+  /// Blank lines are skipped. Every label must already exist in `base`.
+  pub fn load_text(base: &Msbt, text: &str) -> Result<Msbt> {
+    let txt2 = base.txt2.as_ref().ok_or(Error::MissingSection(SectionTag::Txt2))?;
+
+    let mut overrides = BTreeMap::new();
+    for (i, line) in text.lines().enumerate() {
+      if line.trim().is_empty() {
+        continue;
+      }
+      let invalid = || Error::InvalidTextLine(i + 1);
+      let (label, value) = split_line(line).ok_or_else(invalid)?;
+      let label = unescape(label, &['='][..]).ok_or_else(invalid)?;
+      let value = parse_value(value, txt2).ok_or_else(invalid)?;
+      overrides.insert(label, value);
+    }
+
+    let mut msbt = base.clone();
+    if let Some(label) = msbt.apply_overrides(&overrides)?.into_iter().next() {
+      return Err(Error::UnknownLabel(label));
+    }
+
+    Ok(msbt)
+  }
+}
+
+fn escape_char(c: char, out: &mut String) {
+  match c {
+    '\\' => out.push_str("\\\\"),
+    '\n' => out.push_str("\\n"),
+    '\r' => out.push_str("\\r"),
+    c => out.push(c),
+  }
+}
+
+fn escape_label(label: &str, out: &mut String) {
+  for c in label.chars() {
+    match c {
+      '=' => out.push_str("\\="),
+      c => escape_char(c, out),
+    }
+  }
+}
+
+fn escape_value(value: &[Token], txt2: &txt2::Txt2, out: &mut String) -> Result<()> {
+  for (i, token) in value.iter().enumerate() {
+    match token {
+      Token::Text(bytes) => txt2::try_decode_text(bytes, txt2.encoding, txt2.endianness)?
+        .chars()
+        .for_each(|c| escape_char(c, out)),
+      Token::TagStart(group, tag, params) => {
+        out.push_str(&format!("\\{{{},{},{}}}", group, tag, hex::encode(params)));
+      },
+      Token::TagEnd => out.push_str("\\}"),
+      Token::Padding(bytes) if bytes.is_empty() && i == value.len() - 1 => {},
+      Token::Padding(bytes) => out.push_str(&format!("\\0[{}]", hex::encode(bytes))),
+    }
+  }
+  if !matches!(value.last(), Some(Token::Padding(_))) {
+    out.push_str("\\-");
+  }
+
+  Ok(())
+}
+
+/// Split a line at its first unescaped `=`, dropping one space on either side.
+fn split_line(line: &str) -> Option<(&str, &str)> {
+  let mut escaped = false;
+  let eq = line.char_indices()
+    .find(|&(_, c)| {
+      let found = c == '=' && !escaped;
+      escaped = c == '\\' && !escaped;
+      found
+    })
+    .map(|(i, _)| i)?;
+
+  let label = &line[..eq];
+  let value = &line[eq + 1..];
+  Some((
+    label.strip_suffix(' ').unwrap_or(label),
+    value.strip_prefix(' ').unwrap_or(value),
+  ))
+}
+
+fn unescape(s: &str, extra: &[char]) -> Option<String> {
+  let mut out = String::with_capacity(s.len());
+  let mut chars = s.chars();
+  while let Some(c) = chars.next() {
+    if c != '\\' {
+      out.push(c);
+      continue;
+    }
+    match chars.next()? {
+      c if extra.contains(&c) => out.push(c),
+      c => out.push(unescape_char(c)?),
+    }
+  }
+
+  Some(out)
+}
+
+fn unescape_char(c: char) -> Option<char> {
+  match c {
+    '\\' => Some('\\'),
+    'n' => Some('\n'),
+    'r' => Some('\r'),
+    _ => None,
+  }
+}
+
+fn parse_value(s: &str, txt2: &txt2::Txt2) -> Option<Vec<Token>> {
+  let mut tokens = Vec::new();
+  let mut text = String::new();
+  let mut terminated = true;
+  let mut rest = s;
+
+  while let Some(start) = rest.find('\\') {
+    text.push_str(&rest[..start]);
+    let escape = &rest[start + 1..];
+    let next = escape.chars().next()?;
+    rest = &escape[next.len_utf8()..];
+
+    let token = match next {
+      '\\' | 'n' | 'r' => {
+        text.push(unescape_char(next)?);
+        continue;
+      },
+      '-' if rest.is_empty() => {
+        terminated = false;
+        continue;
+      },
+      '{' => {
+        let end = rest.find('}')?;
+        let mut fields = rest[..end].splitn(3, ',');
+        let group = fields.next()?.parse().ok()?;
+        let tag = fields.next()?.parse().ok()?;
+        let params = hex::decode(fields.next()?).ok()?;
+        rest = &rest[end + 1..];
+        Token::TagStart(group, tag, params)
+      },
+      '}' => Token::TagEnd,
+      '0' => {
+        let inner = rest.strip_prefix('[')?;
+        let end = inner.find(']')?;
+        let bytes = hex::decode(&inner[..end]).ok()?;
+        rest = &inner[end + 1..];
+        Token::Padding(bytes)
+      },
+      _ => return None,
+    };
+
+    if !text.is_empty() {
+      tokens.push(Token::from_text(&std::mem::take(&mut text), txt2.encoding, txt2.endianness));
+    }
+    tokens.push(token);
+  }
+  text.push_str(rest);
+
+  if !text.is_empty() {
+    tokens.push(Token::from_text(&text, txt2.encoding, txt2.endianness));
+  }
+  if terminated && !matches!(tokens.last(), Some(Token::Padding(_))) {
+    tokens.push(Token::Padding(Vec::new()));
+  }
+
+  Some(tokens)
+}
@@ -0,0 +1,103 @@
+use crate::{
+  Encoding,
+  Msbt,
+  section::txt2::{self, Token},
+};
+
+use byteordered::Endianness;
+
+use std::collections::BTreeSet;
+
+/// A non-fatal problem found by `Msbt::validate`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Validation {
+  /// More than one label has this name.
+  DuplicateLabel(String),
+  /// No label points at this TXT2 index, usually because two labels in the file shared an index.
+  UnnamedLabel(usize),
+  /// The TXT2 value at this index has no null terminator.
+  UnterminatedValue(usize),
+  /// LBL1 and TXT2 are both present but hold a different number of entries.
+  CountMismatch { labels: usize, messages: usize },
+}
+
+impl Msbt {
+  /// Check this file for problems that do not stop it from being read or written.
+  pub fn validate(&self) -> Vec<Validation> {
+    let mut issues = Vec::new();
+
+    if let Some(lbl1) = &self.lbl1 {
+      let mut seen = BTreeSet::new();
+      let mut reported = BTreeSet::new();
+      for (i, label) in lbl1.labels.iter().enumerate() {
+        if label.name.is_empty() {
+          issues.push(Validation::UnnamedLabel(i));
+        } else if !seen.insert(label.name.as_str()) && reported.insert(label.name.as_str()) {
+          issues.push(Validation::DuplicateLabel(label.name.clone()));
+        }
+      }
+    }
+
+    if let Some(txt2) = &self.txt2 {
+      issues.extend(txt2.unterminated_values().map(Validation::UnterminatedValue));
+    }
+
+    if self.lbl1.is_some() && self.txt2.is_some() && self.label_count() != self.message_count() {
+      issues.push(Validation::CountMismatch { labels: self.label_count(), messages: self.message_count() });
+    }
+
+    issues
+  }
+
+  /// Guess whether the TXT2 text is really in a different encoding from the one in the header, returning
+  /// the encoding it looks like.
+  ///
+  /// A mismatch is only reported when no value reads cleanly in the header's encoding and every value
+  /// does in the other one, so files with unusual but valid content give `None`.
+  pub fn detect_encoding_mismatch(&self) -> Option<Encoding> {
+    let txt2 = self.txt2.as_ref()?;
+    let other = match self.header.encoding {
+      Encoding::Utf8 => Encoding::Utf16,
+      Encoding::Utf16 => Encoding::Utf8,
+      Encoding::Other(_) => return None,
+    };
+
+    let mut judged = 0;
+    for value in txt2.values() {
+      let bytes = txt2::to_bytes(value, txt2.encoding(), txt2.endianness());
+      if bytes.len() < MIN_SNIFF_LEN {
+        continue;
+      }
+      if reads_cleanly(value, txt2.encoding(), txt2.endianness()) {
+        return None;
+      }
+      let reparsed = txt2::parse_bytes(&bytes, other, txt2.endianness());
+      if !reads_cleanly(&reparsed, other, txt2.endianness()) {
+        return None;
+      }
+      judged += 1;
+    }
+
+    if judged > 0 {
+      Some(other)
+    } else {
+      None
+    }
+  }
+}
+
+/// Values shorter than this many bytes are too short to say anything about.
+const MIN_SNIFF_LEN: usize = 4;
+
+/// Whether `value` ends in a plain null terminator and its text decodes without control characters.
+fn reads_cleanly(value: &[Token], encoding: Encoding, endianness: Endianness) -> bool {
+  let terminated = match value.iter().position(|token| matches!(token, Token::Padding(_))) {
+    Some(i) => i == value.len() - 1 && value[i] == Token::Padding(Vec::new()),
+    None => false,
+  };
+  terminated && value.iter().all(|token| match token {
+    Token::Text(bytes) => txt2::try_decode_text(bytes, encoding, endianness)
+      .is_ok_and(|text| text.chars().all(|c| !c.is_control() || c == '\n')),
+    _ => true,
+  })
+}
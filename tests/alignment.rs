@@ -0,0 +1,37 @@
+use byteordered::Endianness;
+use msbt::{builder::MsbtBuilder, Encoding, Msbt, ReaderOptions};
+
+use std::io::Cursor;
+
+fn build(alignment: usize) -> Msbt {
+  MsbtBuilder::new(Endianness::Little, Encoding::Utf16, Some(101))
+    .alignment(alignment)
+    .add_message("Talk_001", "Hello")
+    .add_message("Talk_002", "A slightly longer goodbye")
+    .build()
+    .unwrap()
+}
+
+#[test]
+fn non_default_alignment_round_trips() {
+  let msbt = build(64);
+  let bytes = msbt.to_vec().unwrap();
+  assert_eq!(bytes.len() % 64, 0);
+  assert_eq!(bytes.len(), msbt.total_size());
+
+  let options = ReaderOptions { alignment: Some(64), ..Default::default() };
+  let read = Msbt::from_reader_with_options(Cursor::new(&bytes), options).unwrap();
+  read.verify_size().unwrap();
+  assert_eq!(read.alignment(), 64);
+  assert_eq!(read.to_vec().unwrap(), bytes);
+}
+
+#[test]
+fn padding_is_relative_to_the_start_of_the_file() {
+  // the 32-byte header is not padded, so padding relative to each section would leave TXT2 unaligned
+  let msbt = build(64);
+  let bytes = msbt.to_vec().unwrap();
+  let txt2 = bytes.windows(4).position(|w| w == b"TXT2").unwrap();
+  assert_eq!(txt2 % 64, 0);
+  assert_eq!(msbt.section_size(&msbt::SectionTag::Lbl1), Some(txt2 - 32));
+}
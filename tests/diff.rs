@@ -0,0 +1,73 @@
+use byteordered::Endianness;
+use msbt::{
+  diff::{MessageKey, MsbtDelta, StructuralDelta},
+  section::txt2::Token,
+  Encoding,
+  Msbt,
+  SectionTag,
+};
+
+use std::collections::BTreeMap;
+
+const SAMPLE: &[u8] = include_bytes!("fixtures/sample.msbt");
+
+fn message(text: &str) -> Vec<Token> {
+  vec![Token::from_text(text, Encoding::Utf16, Endianness::Little), Token::Padding(Vec::new())]
+}
+
+fn label(name: &str) -> MessageKey {
+  MessageKey::Label(name.to_string())
+}
+
+#[test]
+fn messages_are_matched_by_label() {
+  let old = Msbt::parse_slice(SAMPLE).unwrap();
+  assert!(old.diff(&old).is_empty());
+
+  let mut new = Msbt::parse_slice(SAMPLE).unwrap();
+  let removed = new.remove_message("Talk_002").unwrap();
+  let mut overrides = BTreeMap::new();
+  overrides.insert("Sign_A".to_string(), message("Come in"));
+  new.apply_overrides(&overrides).unwrap();
+  new.insert_message("Talk_003", message("Bye")).unwrap();
+
+  assert_eq!(old.diff(&new), vec![
+    MsbtDelta::Changed { key: label("Sign_A"), old: message("Keep out"), new: message("Come in") },
+    MsbtDelta::Removed(label("Talk_002")),
+    MsbtDelta::Added(label("Talk_003")),
+  ]);
+  assert_eq!(new.diff(&old), vec![
+    MsbtDelta::Changed { key: label("Sign_A"), old: message("Come in"), new: message("Keep out") },
+    MsbtDelta::Removed(label("Talk_003")),
+    MsbtDelta::Added(label("Talk_002")),
+  ]);
+  assert_eq!(old.txt2().unwrap().values()[1], removed);
+}
+
+#[test]
+fn layout_changes_are_reported() {
+  let old = Msbt::parse_slice(SAMPLE).unwrap();
+  assert!(old.structural_diff(&old).is_empty());
+
+  let mut new = Msbt::parse_slice(SAMPLE).unwrap();
+  new.header_mut().set_unknown_1(7);
+  new.set_pad_byte(0);
+  new.set_alignment(4);
+  new.set_section_order(&[SectionTag::Atr1, SectionTag::Lbl1, SectionTag::Txt2]).unwrap();
+
+  assert_eq!(old.structural_diff(&new), vec![
+    StructuralDelta::HeaderField { field: "unknown_1", old: u32::from(old.header().unknown_1()), new: 7 },
+    StructuralDelta::SectionOrder {
+      old: vec![SectionTag::Lbl1, SectionTag::Atr1, SectionTag::Txt2],
+      new: vec![SectionTag::Atr1, SectionTag::Lbl1, SectionTag::Txt2],
+    },
+    StructuralDelta::PadByte { old: 0xAB, new: 0 },
+    StructuralDelta::Alignment { old: 16, new: 4 },
+  ]);
+
+  new.insert_message("Talk_003", message("Bye")).unwrap();
+  let deltas = old.structural_diff(&new);
+  assert!(deltas.iter().any(|delta| matches!(delta,
+    StructuralDelta::SectionSize { tag: SectionTag::Lbl1, old, new } if old < new)));
+  assert!(deltas.iter().any(|delta| matches!(delta, StructuralDelta::SectionSize { tag: SectionTag::Txt2, .. })));
+}
@@ -0,0 +1,49 @@
+use byteordered::Endianness;
+use msbt::{
+  error::Error,
+  section::txt2::Token,
+  Encoding,
+  Msbt,
+};
+
+use std::collections::BTreeMap;
+
+const SAMPLE: &[u8] = include_bytes!("fixtures/sample.msbt");
+
+fn message(text: &str) -> Vec<Token> {
+  vec![Token::from_text(text, Encoding::Utf16, Endianness::Little), Token::Padding(Vec::new())]
+}
+
+#[test]
+fn overrides_replace_values_by_label() {
+  let mut msbt = Msbt::parse_slice(SAMPLE).unwrap();
+  let mut overrides = BTreeMap::new();
+  overrides.insert("Sign_A".to_string(), message("Come in"));
+  overrides.insert("Talk_009".to_string(), message("Nobody"));
+  overrides.insert("Talk_001".to_string(), message("Hi!"));
+
+  assert_eq!(msbt.apply_overrides(&overrides).unwrap(), vec!["Talk_009".to_string()]);
+  let read = Msbt::parse_slice(&msbt.to_vec().unwrap()).unwrap();
+  let txt2 = read.txt2().unwrap();
+  assert_eq!(txt2.get_string(0, None).unwrap(), "Hi!");
+  assert_eq!(txt2.get_string(2, None).unwrap(), "Come in");
+  assert_eq!(txt2.get_string(3, None).unwrap(), "Main Menu \u{1F600}");
+  assert_eq!(txt2.values().len(), 4);
+}
+
+#[test]
+fn renamed_label_keeps_its_value_and_moves_group() {
+  let mut msbt = Msbt::parse_slice(SAMPLE).unwrap();
+  assert!(matches!(msbt.rename_label("Talk_009", "Sign_B"), Err(Error::UnknownLabel(_))));
+  assert!(matches!(msbt.rename_label("Talk_001", "Sign_A"), Err(Error::DuplicateLabel(_))));
+
+  // Talk_001 hashes to group 26 and Sign_B to group 19
+  msbt.rename_label("Talk_001", "Sign_B").unwrap();
+  let read = Msbt::parse_slice(&msbt.to_vec().unwrap()).unwrap();
+  let lbl1 = read.lbl1().unwrap();
+  assert_eq!(lbl1.index_of("Sign_B"), Some(0));
+  assert_eq!(lbl1.index_of("Talk_001"), None);
+  assert_eq!(lbl1.groups()[26].label_count(), 0);
+  assert_eq!(lbl1.groups()[19].label_count(), 1);
+  assert_eq!(read.txt2().unwrap().get_string(0, None).unwrap(), "Hello there!");
+}
@@ -0,0 +1,43 @@
+use byteordered::Endianness;
+use msbt::{section::txt2::Token, Encoding, Msbt};
+
+/// A UTF-16 file with an LBL1 and a TXT2 that both have a size of 0.
+fn bare_file() -> Vec<u8> {
+  let mut bytes = Vec::new();
+  bytes.extend_from_slice(b"MsgStdBn");
+  bytes.extend_from_slice(&[0xFF, 0xFE, 0, 0, 1, 3, 2, 0, 0, 0]);
+  bytes.extend_from_slice(&64u32.to_le_bytes());
+  bytes.extend_from_slice(&[0; 10]);
+  for magic in &[b"LBL1", b"TXT2"] {
+    bytes.extend_from_slice(&magic[..]);
+    bytes.extend_from_slice(&[0; 12]);
+  }
+  bytes
+}
+
+#[test]
+fn bare_sections_round_trip() {
+  let bytes = bare_file();
+  let msbt = Msbt::parse_slice(&bytes).unwrap();
+  msbt.verify_size().unwrap();
+  assert!(msbt.lbl1().unwrap().labels().is_empty());
+  assert!(msbt.txt2().unwrap().values().is_empty());
+  assert_eq!(msbt.to_vec().unwrap(), bytes);
+}
+
+#[test]
+fn insert_into_bare_lbl1() {
+  let mut msbt = Msbt::parse_slice(&bare_file()).unwrap();
+  let value = vec![Token::from_text("Hello", Encoding::Utf16, Endianness::Little), Token::Padding(Vec::new())];
+  msbt.insert_message("Talk_001", value).unwrap();
+  msbt.insert_nli1_message(7, vec![Token::Padding(Vec::new())]).unwrap();
+  assert_eq!(msbt.lbl1().unwrap().groups().len(), 101);
+
+  let bytes = msbt.to_vec().unwrap();
+  let read = Msbt::parse_slice(&bytes).unwrap();
+  read.verify_size().unwrap();
+  assert_eq!(read.lbl1().unwrap().labels()[0].name(), "Talk_001");
+  assert_eq!(read.txt2().unwrap().get_string(0, None).unwrap(), "Hello");
+  assert_eq!(read.nli1().unwrap().txt2_index(7), Some(1));
+  assert_eq!(read.to_vec().unwrap(), bytes);
+}
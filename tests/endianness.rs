@@ -0,0 +1,107 @@
+use byteordered::Endianness;
+use msbt::{
+  builder::MsbtBuilder,
+  section::{txt2::Token, Ato1, Atr1, Tsy1, Txt2},
+  Encoding,
+  Msbt,
+};
+
+fn build(endianness: Endianness) -> Msbt {
+  let mut atr1 = Atr1::new_unlinked(Vec::new());
+  atr1.set_entry_count(2, &[1, 2, 3, 4], endianness).unwrap();
+  MsbtBuilder::new(endianness, Encoding::Utf16, Some(101))
+    .add_message("Talk_001", "Hello")
+    .add_message("Talk_002", "Bye")
+    .ato1(Ato1::from_offsets(&[-1, 0x1234], endianness))
+    .atr1(atr1)
+    .tsy1(Tsy1::from_indices(&[3, 0x10000], endianness))
+    .build()
+    .unwrap()
+}
+
+#[test]
+fn set_endianness_round_trips() {
+  let little = build(Endianness::Little);
+  let bytes = little.to_vec().unwrap();
+
+  let mut msbt = Msbt::parse_slice(&bytes).unwrap();
+  msbt.set_endianness(Endianness::Big);
+  let big_bytes = msbt.to_vec().unwrap();
+  assert_eq!(big_bytes, build(Endianness::Big).to_vec().unwrap());
+
+  let mut big = Msbt::parse_slice(&big_bytes).unwrap();
+  assert_eq!(big.header().endianness(), Endianness::Big);
+  assert_eq!(big.ato1().unwrap().offsets(Endianness::Big), vec![-1, 0x1234]);
+  assert_eq!(big.tsy1().unwrap().style_indices(Endianness::Big), vec![3, 0x10000]);
+  assert_eq!(big.atr1().unwrap().entry_count(Endianness::Big), 2);
+  assert_eq!(big.atr1().unwrap().attribute_size(Endianness::Big), 4);
+  assert_eq!(big.txt2().unwrap().get_string(0, None).unwrap(), "Hello");
+
+  big.set_endianness(Endianness::Little);
+  assert_eq!(big.to_vec().unwrap(), bytes);
+}
+
+#[test]
+fn byte_order_mark_follows_endianness() {
+  let mut msbt = build(Endianness::Little);
+  for &(endianness, bom) in &[(Endianness::Big, [0xFE, 0xFF]), (Endianness::Little, [0xFF, 0xFE])] {
+    msbt.set_endianness(endianness);
+    let bytes = msbt.to_vec().unwrap();
+    assert_eq!(&bytes[8..10], &bom);
+    assert_eq!(Msbt::parse_slice(&bytes).unwrap().header().endianness(), endianness);
+  }
+}
+
+#[test]
+fn set_endianness_swaps_control_code_parameters() {
+  let params = |endianness| {
+    let tokens = vec![Token::TagStart(0, 3, u16_bytes(0x1234, endianness)), Token::Padding(Vec::new())];
+    MsbtBuilder::new(endianness, Encoding::Utf16, Some(101))
+      .add_label("Talk_001", tokens)
+      .build()
+      .unwrap()
+  };
+
+  let mut little = params(Endianness::Little);
+  little.set_endianness(Endianness::Big);
+  assert_eq!(little.to_vec().unwrap(), params(Endianness::Big).to_vec().unwrap());
+}
+
+fn u16_bytes(value: u16, endianness: Endianness) -> Vec<u8> {
+  match endianness {
+    Endianness::Big => value.to_be_bytes().to_vec(),
+    Endianness::Little => value.to_le_bytes().to_vec(),
+  }
+}
+
+#[test]
+fn builder_endianness_swaps_attribute_sections() {
+  let endianness = Endianness::Little;
+  let mut atr1 = Atr1::new_unlinked(Vec::new());
+  atr1.set_entry_count(2, &[1, 2, 3, 4], endianness).unwrap();
+  let msbt = MsbtBuilder::new(endianness, Encoding::Utf16, Some(101))
+    .add_message("Talk_001", "Hello")
+    .add_message("Talk_002", "Bye")
+    .ato1(Ato1::from_offsets(&[-1, 0x1234], endianness))
+    .atr1(atr1)
+    .tsy1(Tsy1::from_indices(&[3, 0x10000], endianness))
+    .endianness(Endianness::Big)
+    .build()
+    .unwrap();
+  assert_eq!(msbt.to_vec().unwrap(), build(Endianness::Big).to_vec().unwrap());
+}
+
+#[test]
+fn builder_converts_a_supplied_txt2() {
+  let txt2 = Txt2::from_strings(["Hello", "Bye"], Encoding::Utf8, Endianness::Big);
+  let msbt = MsbtBuilder::new(Endianness::Little, Encoding::Utf16, None)
+    .txt2(txt2)
+    .build()
+    .unwrap();
+  let txt2 = msbt.txt2().unwrap();
+  assert_eq!((txt2.encoding(), txt2.endianness()), (Encoding::Utf16, Endianness::Little));
+  assert_eq!(txt2, &Txt2::from_strings(["Hello", "Bye"], Encoding::Utf16, Endianness::Little));
+
+  let read = Msbt::parse_slice(&msbt.to_vec().unwrap()).unwrap();
+  assert_eq!(read.txt2().unwrap().get_string(1, None).unwrap(), "Bye");
+}
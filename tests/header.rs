@@ -0,0 +1,42 @@
+use byteordered::Endianness;
+use msbt::{error::Error, Encoding, Header, Msbt};
+
+use std::io::Cursor;
+
+const SAMPLE: &[u8] = include_bytes!("fixtures/sample.msbt");
+
+#[test]
+fn known_good_header() {
+  let header = Header::from_reader(&mut &SAMPLE[..32]).unwrap();
+  assert_eq!(&header.magic(), b"MsgStdBn");
+  assert_eq!(header.endianness(), Endianness::Little);
+  assert_eq!(header.encoding(), Encoding::Utf16);
+  assert_eq!(header.section_count(), 3);
+  assert_eq!(header.declared_file_size() as usize, SAMPLE.len());
+}
+
+#[test]
+fn reversed_magic_is_rejected() {
+  let mut bytes = SAMPLE[..32].to_vec();
+  bytes[..8].reverse();
+  assert!(matches!(Header::from_reader(&mut &bytes[..]), Err(Error::InvalidMagic { offset: 0 })));
+}
+
+#[test]
+fn invalid_magic_reports_the_stream_position() {
+  let mut bytes = vec![0; 16];
+  bytes.extend_from_slice(SAMPLE);
+  bytes[16..24].reverse();
+  let mut cursor = Cursor::new(&bytes);
+  cursor.set_position(16);
+  assert!(matches!(Msbt::from_reader(cursor), Err(Error::InvalidMagic { offset: 16 })));
+}
+
+#[test]
+fn declared_size_override_does_not_fail_the_written_size_check() {
+  let mut msbt = Msbt::parse_slice(SAMPLE).unwrap();
+  msbt.set_declared_size_override(Some(7));
+  let bytes = msbt.to_vec().unwrap();
+  assert_eq!(bytes.len(), SAMPLE.len());
+  assert_eq!(&bytes[0x12..0x16], &7u32.to_le_bytes());
+}
@@ -0,0 +1,57 @@
+use byteordered::Endianness;
+use msbt::{builder::MsbtBuilder, section::Txt2, Encoding, Msbt};
+
+use std::{
+  alloc::{GlobalAlloc, Layout, System},
+  io::Cursor,
+  sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Tracks the bytes currently allocated. This file has a single test, so nothing else allocates meanwhile.
+struct Counting;
+
+static LIVE: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+  unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+    LIVE.fetch_add(layout.size(), Ordering::SeqCst);
+    System.alloc(layout)
+  }
+
+  unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+    LIVE.fetch_sub(layout.size(), Ordering::SeqCst);
+    System.dealloc(ptr, layout)
+  }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+/// The bytes still allocated by the value `f` returns.
+fn retained<T>(f: impl FnOnce() -> T) -> (T, usize) {
+  let before = LIVE.load(Ordering::SeqCst);
+  let value = f();
+  (value, LIVE.load(Ordering::SeqCst).saturating_sub(before))
+}
+
+#[test]
+fn lazy_reading_uses_less_memory() {
+  // TXT2 only, so labels do not take up the same memory either way
+  let strings: Vec<String> = (0..20_000).map(|i| format!("Message {}", i)).collect();
+  let bytes = MsbtBuilder::new(Endianness::Little, Encoding::Utf16, None)
+    .txt2(Txt2::from_strings(&strings, Encoding::Utf16, Endianness::Little))
+    .build()
+    .unwrap()
+    .to_vec()
+    .unwrap();
+  assert!(bytes.len() > 400_000);
+
+  let (eager, eager_bytes) = retained(|| Msbt::from_reader(Cursor::new(&bytes)).unwrap());
+  let (lazy, lazy_bytes) = retained(|| Msbt::from_reader_lazy(Cursor::new(&bytes)).unwrap());
+  assert!(lazy_bytes * 2 < eager_bytes, "lazy: {} bytes, eager: {} bytes", lazy_bytes, eager_bytes);
+
+  let txt2 = lazy.txt2().unwrap();
+  assert_eq!(txt2.len(), 20_000);
+  assert_eq!(txt2.decode(12_345).as_deref(), eager.txt2().unwrap().tokens(12_345));
+  assert_eq!(lazy.into_msbt(), eager);
+}
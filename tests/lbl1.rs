@@ -0,0 +1,126 @@
+use byteordered::Endianness;
+use msbt::{
+  builder::MsbtBuilder,
+  error::Error,
+  section::{txt2::Token, Lbl1, Txt2},
+  Encoding,
+  Msbt,
+  ReaderOptions,
+};
+
+use std::io::Cursor;
+
+#[test]
+fn zero_group_count_is_rejected() {
+  assert!(matches!(Lbl1::with_group_count(&["a"], &[0], 0), Err(Error::InvalidGroupCount(0))));
+
+  let built = MsbtBuilder::new(Endianness::Little, Encoding::Utf16, Some(0))
+    .add_message("Talk_001", "Hello")
+    .build();
+  assert!(matches!(built, Err(Error::InvalidGroupCount(0))));
+
+  let mut msbt = Msbt::new(Endianness::Little, Encoding::Utf16);
+  assert!(matches!(msbt.ensure_lbl1(0), Err(Error::InvalidGroupCount(0))));
+  assert!(msbt.lbl1().is_none());
+}
+
+#[test]
+fn read_order_is_not_compared() {
+  let built = MsbtBuilder::new(Endianness::Little, Encoding::Utf16, Some(101))
+    .add_message("Talk_001", "Hello")
+    .add_message("Talk_002", "Bye")
+    .build()
+    .unwrap();
+  let read = Msbt::parse_slice(&built.to_vec().unwrap()).unwrap();
+  assert_eq!(read.lbl1(), built.lbl1());
+}
+
+#[test]
+fn hash_matches_known_pairs() {
+  for &(name, group_count, hash) in &[
+    ("a", 101, 97),
+    ("Talk_001", 101, 26),
+    ("Sign_A", 101, 18),
+    ("Menu_Title", 101, 88),
+    ("Menu_Title", 1000, 909),
+    ("", 101, 0),
+  ] {
+    assert_eq!(Lbl1::hash(name, group_count), hash, "{}", name);
+  }
+}
+
+#[test]
+fn labels_in_sample_are_in_their_hash_group() {
+  let msbt = Msbt::parse_slice(include_bytes!("fixtures/sample.msbt")).unwrap();
+  let lbl1 = msbt.lbl1().unwrap();
+  let group_count = lbl1.groups().len() as u32;
+  for (i, label) in lbl1.labels().iter().enumerate() {
+    assert_eq!(lbl1.group_of(i), Some(Lbl1::hash(label.name(), group_count) as usize), "{}", label.name());
+  }
+}
+
+#[test]
+fn label_index_out_of_range() {
+  let mut bytes = include_bytes!("fixtures/sample.msbt").to_vec();
+  let name = bytes.windows(10).position(|w| w == b"Menu_Title").unwrap();
+  bytes[name + 10..name + 14].copy_from_slice(&9u32.to_le_bytes());
+
+  assert!(matches!(Msbt::parse_slice(&bytes), Err(Error::LabelIndexOutOfRange { index: 9, count: 4 })));
+
+  let options = ReaderOptions { grow_labels: true, ..Default::default() };
+  let msbt = Msbt::from_reader_with_options(Cursor::new(&bytes), options).unwrap();
+  let lbl1 = msbt.lbl1().unwrap();
+  assert_eq!(lbl1.labels().len(), 10);
+  assert_eq!(lbl1.index_of("Menu_Title"), Some(9));
+}
+
+#[test]
+fn unnamed_labels_are_written_with_empty_names() {
+  let mut msbt = MsbtBuilder::new(Endianness::Little, Encoding::Utf16, None)
+    .txt2(Txt2::from_strings(["Hello", "Bye"], Encoding::Utf16, Endianness::Little))
+    .build()
+    .unwrap();
+  msbt.ensure_lbl1(101).unwrap();
+  msbt.insert_message("Talk_003", vec![Token::Padding(Vec::new())]).unwrap();
+
+  let bytes = msbt.to_vec().unwrap();
+  let read = Msbt::parse_slice(&bytes).unwrap();
+  read.verify_size().unwrap();
+  let lbl1 = read.lbl1().unwrap();
+  assert_eq!(lbl1.labels().len(), 3);
+  assert_eq!(lbl1.groups()[0].label_count(), 2);
+  assert_eq!(lbl1.labels()[0].name(), "");
+  assert_eq!(lbl1.label_names().collect::<Vec<_>>(), ["Talk_003"]);
+  assert_eq!(lbl1.index_of(""), None);
+  assert_eq!(read.to_vec().unwrap(), bytes);
+}
+
+#[test]
+fn label_names_over_255_bytes_are_rejected() {
+  let long = "a".repeat(256);
+  let max = "a".repeat(255);
+
+  let built = MsbtBuilder::new(Endianness::Little, Encoding::Utf16, Some(101))
+    .add_message(long.as_str(), "Hi")
+    .build();
+  assert!(matches!(built, Err(Error::LabelTooLong(ref name)) if *name == long));
+
+  let mut msbt = Msbt::parse_slice(include_bytes!("fixtures/sample.msbt")).unwrap();
+  let value = || vec![Token::Padding(Vec::new())];
+  assert!(matches!(msbt.insert_message(&long, value()), Err(Error::LabelTooLong(_))));
+  assert!(matches!(msbt.rename_label("Talk_001", &long), Err(Error::LabelTooLong(_))));
+  assert!(matches!(Lbl1::with_group_count(&[&long], &[0], 101), Err(Error::LabelTooLong(_))));
+
+  msbt.insert_message(&max, value()).unwrap();
+  let read = Msbt::parse_slice(&msbt.to_vec().unwrap()).unwrap();
+  assert_eq!(read.lbl1().unwrap().index_of(&max), Some(4));
+  assert_eq!(read.lbl1().unwrap().index_of("Talk_001"), Some(0));
+}
+
+#[test]
+fn over_long_name_set_directly_is_rejected_when_writing() {
+  let long = "a".repeat(300);
+  let mut msbt = Msbt::parse_slice(include_bytes!("fixtures/sample.msbt")).unwrap();
+  msbt.lbl1_mut().unwrap().labels_mut()[1].set_name(long.as_str());
+  assert!(matches!(msbt.to_vec(), Err(Error::LabelTooLong(ref name)) if *name == long));
+}
@@ -0,0 +1,43 @@
+use byteordered::Endianness;
+use msbt::{builder::MsbtBuilder, section::Nli1, Encoding, Msbt};
+
+use std::collections::BTreeMap;
+
+#[test]
+fn reverse_lookup_follows_mutations() {
+  let mut nli1 = Nli1::new_unlinked(0, BTreeMap::new());
+  nli1.insert(0, 10);
+  nli1.insert(1, 20);
+  nli1.insert(2, 10);
+  assert_eq!(nli1.txt2_index(10), Some(0));
+  assert_eq!(nli1.txt2_index(20), Some(1));
+  assert_eq!(nli1.id_for_index(2), Some(10));
+
+  assert_eq!(nli1.remove(0), Some(10));
+  assert_eq!(nli1.txt2_index(10), Some(2));
+
+  assert_eq!(nli1.insert(1, 30), Some(20));
+  assert_eq!(nli1.txt2_index(20), None);
+  assert_eq!(nli1.txt2_index(30), Some(1));
+  assert_eq!(nli1.entries().collect::<Vec<_>>(), [(30, 1), (10, 2)]);
+}
+
+#[test]
+fn written_id_count_follows_entries() {
+  let mut msbt = MsbtBuilder::new(Endianness::Little, Encoding::Utf16, None)
+    .add_message("Talk_001", "Hello")
+    .add_message("Talk_002", "Bye")
+    .with_nli1_id(7, 0)
+    .build()
+    .unwrap();
+  msbt.nli1_mut().unwrap().insert(1, 8);
+
+  let bytes = msbt.to_vec().unwrap();
+  let nli1 = bytes.windows(4).position(|w| w == b"NLI1").unwrap();
+  assert_eq!(&bytes[nli1 + 4..nli1 + 8], &20u32.to_le_bytes());
+  assert_eq!(&bytes[nli1 + 16..nli1 + 20], &2u32.to_le_bytes());
+
+  let read = Msbt::parse_slice(&bytes).unwrap();
+  assert_eq!(read.nli1().unwrap().id_count(), 2);
+  assert_eq!(read.nli1().unwrap().txt2_index(8), Some(1));
+}
@@ -0,0 +1,81 @@
+use byteordered::Endianness;
+use msbt::{builder::MsbtBuilder, Encoding, Msbt, ReaderOptions};
+
+use std::io::Cursor;
+
+fn strict() -> ReaderOptions {
+  ReaderOptions { strict_padding: true, ..Default::default() }
+}
+
+/// A file whose LBL1 padding is all 0xAB and whose TXT2 padding is all 0x00.
+fn mixed_padding() -> Vec<u8> {
+  let msbt = MsbtBuilder::new(Endianness::Little, Encoding::Utf16, Some(101))
+    .pad_byte(0xAB)
+    .add_message("Talk_001", "Hi")
+    .build()
+    .unwrap();
+  let mut bytes = msbt.to_vec().unwrap();
+
+  let txt2 = bytes.windows(4).position(|w| w == b"TXT2").unwrap();
+  let txt2_size = u32::from_le_bytes([bytes[txt2 + 4], bytes[txt2 + 5], bytes[txt2 + 6], bytes[txt2 + 7]]);
+  let txt2_end = txt2 + 16 + txt2_size as usize;
+  assert!(txt2_end < bytes.len(), "TXT2 needs padding for this test");
+  assert!(bytes[txt2 - 1] == 0xAB, "LBL1 needs padding for this test");
+  for b in &mut bytes[txt2_end..] {
+    *b = 0;
+  }
+  bytes
+}
+
+#[test]
+fn strict_padding_keeps_uniform_padding_that_differs_between_sections() {
+  let bytes = mixed_padding();
+  let msbt = Msbt::from_reader_with_options(Cursor::new(&bytes), strict()).unwrap();
+  assert_eq!(msbt.to_vec().unwrap(), bytes);
+}
+
+#[test]
+fn strict_padding_keeps_non_uniform_padding() {
+  let mut bytes = mixed_padding();
+  let last = bytes.len() - 1;
+  bytes[last] = 0x12;
+  let msbt = Msbt::from_reader_with_options(Cursor::new(&bytes), strict()).unwrap();
+  assert_eq!(msbt.to_vec().unwrap(), bytes);
+}
+
+#[test]
+fn written_padding_uses_pad_byte() {
+  let bytes = MsbtBuilder::new(Endianness::Little, Encoding::Utf16, Some(101))
+    .pad_byte(0xAB)
+    .add_message("Talk_001", "Hi")
+    .build()
+    .unwrap()
+    .to_vec()
+    .unwrap();
+  let txt2 = bytes.windows(4).position(|w| w == b"TXT2").unwrap();
+  assert_eq!(txt2, 0x370);
+  // LBL1 ends at 0x369 here, so it is followed by 7 bytes of padding
+  assert_eq!(&bytes[txt2 - 7..txt2], &[0xAB; 7]);
+  assert_eq!(Msbt::parse_slice(&bytes).unwrap().pad_byte(), 0xAB);
+}
+
+#[test]
+fn set_pad_byte_changes_written_padding() {
+  let mut msbt = Msbt::parse_slice(include_bytes!("fixtures/sample.msbt")).unwrap();
+  msbt.set_pad_byte(0x00);
+  let bytes = msbt.to_vec().unwrap();
+  assert_eq!(&bytes[bytes.len() - 10..], &[0; 10]);
+}
+
+#[test]
+fn missing_final_padding() {
+  let sample = include_bytes!("fixtures/sample.msbt");
+  // the sample ends with 10 bytes of padding after TXT2
+  let truncated = &sample[..sample.len() - 10];
+
+  let msbt = Msbt::parse_slice(truncated).unwrap();
+  assert_eq!(msbt.txt2().unwrap().values().len(), 4);
+  assert_eq!(msbt.to_vec().unwrap(), &sample[..]);
+
+  assert!(Msbt::from_reader_with_options(Cursor::new(truncated), strict()).is_err());
+}
@@ -0,0 +1,40 @@
+use msbt::{Encoding, Msbt, SectionTag};
+
+/// A little-endian UTF-16 file laid out like Nintendo's: LBL1 with 101 groups, an empty ATR1 and TXT2,
+/// padded with 0xAB, with a control code in one message and a surrogate pair in another.
+const SAMPLE: &[u8] = include_bytes!("fixtures/sample.msbt");
+
+#[test]
+fn sample_round_trips_byte_for_byte() {
+  let msbt = Msbt::parse_slice(SAMPLE).unwrap();
+  msbt.verify_size().unwrap();
+  assert_eq!(msbt.to_vec().unwrap(), SAMPLE);
+}
+
+#[test]
+fn sample_contents() {
+  let msbt = Msbt::parse_slice(SAMPLE).unwrap();
+  assert_eq!(msbt.header().encoding(), Encoding::Utf16);
+  assert_eq!(msbt.section_order(), &[SectionTag::Lbl1, SectionTag::Atr1, SectionTag::Txt2]);
+  assert_eq!(msbt.pad_byte(), 0xAB);
+
+  let lbl1 = msbt.lbl1().unwrap();
+  assert_eq!(lbl1.groups().len(), 101);
+  let names: Vec<&str> = lbl1.label_names().collect();
+  assert_eq!(names, ["Talk_001", "Talk_002", "Sign_A", "Menu_Title"]);
+
+  let txt2 = msbt.txt2().unwrap();
+  assert_eq!(txt2.get_string(0, None).unwrap(), "Hello there!");
+  assert_eq!(txt2.get_string(1, Some("<>")).unwrap(), "See you, <>friend.");
+  assert_eq!(txt2.get_string(3, None).unwrap(), "Main Menu \u{1F600}");
+}
+
+#[test]
+fn sample_reads_lazily() {
+  let lazy = Msbt::from_reader_lazy(std::io::Cursor::new(SAMPLE)).unwrap();
+  let eager = Msbt::parse_slice(SAMPLE).unwrap();
+  assert!(lazy.msbt().txt2().is_none());
+  assert_eq!(lazy.get("Talk_002").as_deref(), eager.txt2().unwrap().tokens(1));
+  assert_eq!(lazy.get("Missing"), None);
+  assert_eq!(lazy.into_msbt().to_vec().unwrap(), SAMPLE);
+}
@@ -0,0 +1,49 @@
+use msbt::{error::Error, Msbt, ReaderOptions, SectionTag};
+
+use std::io::Cursor;
+
+const SAMPLE: &[u8] = include_bytes!("fixtures/sample.msbt");
+
+/// The sample with `sections` appended and the header's section count and file size updated.
+fn with_extra_sections(sections: &[&[u8]]) -> Vec<u8> {
+  let mut bytes = SAMPLE.to_vec();
+  for section in sections {
+    bytes.extend_from_slice(section);
+  }
+  let count = 3 + sections.len() as u16;
+  bytes[0x0E..0x10].copy_from_slice(&count.to_le_bytes());
+  let size = bytes.len() as u32;
+  bytes[0x12..0x16].copy_from_slice(&size.to_le_bytes());
+  bytes
+}
+
+#[test]
+fn duplicate_section_is_rejected() {
+  let atr1 = SAMPLE.windows(4).position(|w| w == b"ATR1").unwrap();
+  let bytes = with_extra_sections(&[&SAMPLE[atr1..atr1 + 32]]);
+  assert!(matches!(
+    Msbt::parse_slice(&bytes),
+    Err(Error::DuplicateSection { tag: SectionTag::Atr1, offset }) if offset as usize == SAMPLE.len()
+  ));
+}
+
+#[test]
+fn more_than_six_sections() {
+  let unknown: Vec<[u8; 16]> = (0..5)
+    .map(|i| {
+      let mut section = [0; 16];
+      section[..4].copy_from_slice(&[b'X', b'Y', b'Z', b'0' + i]);
+      section
+    })
+    .collect();
+  let unknown: Vec<&[u8]> = unknown.iter().map(|section| &section[..]).collect();
+  let bytes = with_extra_sections(&unknown);
+
+  let options = ReaderOptions { keep_unknown_sections: true, ..Default::default() };
+  let msbt = Msbt::from_reader_with_options(Cursor::new(&bytes), options).unwrap();
+  assert_eq!(msbt.section_order().len(), 8);
+  assert_eq!(msbt.unknown_sections().len(), 5);
+  assert_eq!(msbt.section_order()[7], SectionTag::Unknown(*b"XYZ4"));
+  msbt.verify_size().unwrap();
+  assert_eq!(msbt.to_vec().unwrap(), bytes);
+}
@@ -0,0 +1,41 @@
+use byteordered::Endianness;
+use msbt::{
+  builder::MsbtBuilder,
+  section::txt2::Token,
+  Encoding,
+  Msbt,
+};
+
+fn text(s: &str) -> Token {
+  Token::from_text(s, Encoding::Utf16, Endianness::Little)
+}
+
+fn build(values: Vec<Vec<Token>>) -> Msbt {
+  let labels = ["Talk=001", "Line\\Break\n", "{Brace}"];
+  values.into_iter()
+    .zip(labels.iter())
+    .fold(MsbtBuilder::new(Endianness::Little, Encoding::Utf16, Some(101)), |builder, (value, label)| {
+      builder.add_label(*label, value)
+    })
+    .build()
+    .unwrap()
+}
+
+#[test]
+fn dumped_text_loads_back() {
+  let original = build(vec![
+    vec![text("a = b\nc\r\\d {e} \\{f}"), Token::Padding(Vec::new())],
+    vec![text("Hi "), Token::TagStart(0, 3, vec![0x12, 0x34]), text("bold"), Token::TagEnd, Token::Padding(Vec::new())],
+    vec![text("="), Token::Padding(vec![0xAB, 0xCD])],
+  ]);
+  let dumped = original.dump_text().unwrap();
+  assert_eq!(dumped.lines().count(), 3);
+  assert!(dumped.contains("Talk\\=001 = a = b\\nc\\r\\\\d {e} \\\\{f}\n"));
+  assert!(dumped.contains("Line\\\\Break\\n = Hi \\{0,3,1234}bold\\}\n"));
+  assert!(dumped.contains("{Brace} = =\\0[abcd]\n"));
+
+  let base = build(vec![vec![text("x")], vec![text("y")], vec![text("z")]]);
+  let loaded = Msbt::load_text(&base, &dumped).unwrap();
+  assert_eq!(loaded.txt2(), original.txt2());
+  assert_eq!(loaded.to_vec().unwrap(), original.to_vec().unwrap());
+}
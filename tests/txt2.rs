@@ -0,0 +1,113 @@
+use byteordered::Endianness;
+use msbt::{
+  builder::MsbtBuilder,
+  error::Error,
+  section::txt2::{self, Token},
+  Encoding,
+  Msbt,
+};
+
+use std::{
+  collections::hash_map::DefaultHasher,
+  hash::{Hash, Hasher},
+};
+
+fn hash<T: Hash>(value: &T) -> u64 {
+  let mut hasher = DefaultHasher::new();
+  value.hash(&mut hasher);
+  hasher.finish()
+}
+
+#[test]
+fn read_offsets_are_not_compared() {
+  let built = MsbtBuilder::new(Endianness::Little, Encoding::Utf16, Some(101))
+    .add_message("Talk_001", "Hello")
+    .add_message("Talk_002", "Bye")
+    .build()
+    .unwrap();
+  let read = Msbt::parse_slice(&built.to_vec().unwrap()).unwrap();
+  let (built, read) = (built.txt2().unwrap(), read.txt2().unwrap());
+  assert_ne!(read.read_offsets(), built.read_offsets());
+  assert_eq!(read, built);
+  assert_eq!(hash(read), hash(built));
+}
+
+#[test]
+fn surrogate_pairs_round_trip() {
+  let text = "Smile \u{1F600}!";
+  let built = MsbtBuilder::new(Endianness::Little, Encoding::Utf16, Some(101))
+    .add_message("Talk_001", text)
+    .build()
+    .unwrap();
+  let bytes = built.to_vec().unwrap();
+  // U+1F600 is written as the surrogate pair D83D DE00
+  assert!(bytes.windows(4).any(|w| w == [0x3D, 0xD8, 0x00, 0xDE]));
+
+  let read = Msbt::parse_slice(&bytes).unwrap();
+  assert_eq!(read.txt2().unwrap().get_string(0, None).unwrap(), text);
+  assert_eq!(txt2::decode(&txt2::encode(text, Encoding::Utf16, Endianness::Big), Encoding::Utf16, Endianness::Big).unwrap(), text);
+}
+
+#[test]
+fn lone_surrogates_are_invalid_utf16() {
+  let bytes = [0x3D, 0xD8, b'a', 0, 0, 0];
+  assert!(matches!(txt2::decode(&bytes, Encoding::Utf16, Endianness::Little), Err(Error::InvalidUtf16(_))));
+}
+
+#[test]
+fn longer_value_updates_size_and_offsets() {
+  let mut msbt = Msbt::parse_slice(include_bytes!("fixtures/sample.msbt")).unwrap();
+  let old_size = msbt.txt2().unwrap().section().size();
+  let old_offsets = msbt.txt2().unwrap().computed_offsets();
+  {
+    let mut txt2 = msbt.txt2_mut().unwrap();
+    // "Keep out" becomes 10 characters, 4 bytes longer in UTF-16
+    let text = Token::from_text("Keep out!!", Encoding::Utf16, Endianness::Little);
+    txt2.values_mut()[2] = vec![text, Token::Padding(Vec::new())];
+  }
+  let txt2 = msbt.txt2().unwrap();
+  assert_eq!(txt2.section().size(), old_size + 4);
+  assert_eq!(txt2.computed_offsets()[..3], old_offsets[..3]);
+  assert_eq!(txt2.computed_offsets()[3], old_offsets[3] + 4);
+
+  let read = Msbt::parse_slice(&msbt.to_vec().unwrap()).unwrap();
+  read.verify_size().unwrap();
+  let txt2 = read.txt2().unwrap();
+  assert_eq!(txt2.section().size(), old_size + 4);
+  assert_eq!(txt2.read_offsets(), &txt2.computed_offsets()[..]);
+  assert_eq!(txt2.get_string(2, None).unwrap(), "Keep out!!");
+  assert_eq!(txt2.get_string(3, None).unwrap(), "Main Menu \u{1F600}");
+}
+
+#[test]
+fn padding_before_text_is_not_a_terminator() {
+  let mut txt2 = txt2::Txt2::from_strings(["Hi"], Encoding::Utf16, Endianness::Little);
+  let text = Token::Text(vec![b'!', 0]);
+  txt2.values_mut()[0] = vec![Token::Padding(Vec::new()), text.clone()];
+  assert_eq!(txt2.unterminated_values().collect::<Vec<_>>(), vec![0]);
+  assert_eq!(txt2.terminate_values(), 1);
+  assert_eq!(txt2.values()[0], vec![Token::Padding(Vec::new()), text.clone(), Token::Padding(Vec::new())]);
+
+  let msbt = MsbtBuilder::new(Endianness::Little, Encoding::Utf16, Some(101))
+    .add_label("Talk_001", vec![Token::Padding(Vec::new()), text.clone()])
+    .build()
+    .unwrap();
+  assert_eq!(msbt.txt2().unwrap().values()[0], vec![Token::Padding(Vec::new()), text, Token::Padding(Vec::new())]);
+}
+
+#[test]
+fn truncating_inside_a_tag_drops_the_tag() {
+  let text = |s: &str| Token::from_text(s, Encoding::Utf16, Endianness::Little);
+  let value = vec![text("Hi "), Token::TagStart(0, 3, Vec::new()), text("bold"), Token::TagEnd, Token::Padding(Vec::new())];
+  let mut txt2 = txt2::Txt2::from_strings(["x"], Encoding::Utf16, Endianness::Little);
+  txt2.values_mut()[0] = value.clone();
+  let full = txt2.byte_len(0).unwrap();
+
+  assert_eq!(txt2.truncate_to_bytes(0, full).unwrap(), 0);
+  assert_eq!(txt2.values()[0], value);
+
+  let cut = full - 4;
+  assert_eq!(txt2.truncate_to_bytes(0, cut).unwrap(), full - 8);
+  assert_eq!(txt2.values()[0], vec![text("Hi "), Token::Padding(Vec::new())]);
+  assert_eq!(txt2.byte_len(0).unwrap(), 8);
+}